@@ -1,8 +1,23 @@
-use crate::de::Deserializer;
+use crate::capture::to_tokens;
+use crate::config::Config;
+use crate::configure::{Compact, Configure, Readable};
+use crate::de::{Deserializer, ExpectingDeserializer};
+use crate::error::{Error, ErrorKind};
+use crate::memory::peak_memory;
+use crate::mismatch::{compare_tokens, Mismatch};
+use crate::output::{catch_failure, fail, record_failures, take_failures};
+use crate::report::Report;
 use crate::ser::Serializer;
-use crate::token::Token;
+use crate::token::{value_len, Token};
+use crate::trace::{dedent, ser_trace};
+use serde::de::{DeserializeOwned, DeserializeSeed, Unexpected};
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
+use std::iter;
+use std::ops::Range;
+use std::panic;
+use std::sync::{Arc, Barrier};
+use std::thread;
 
 /// Runs both `assert_ser_tokens` and `assert_de_tokens`.
 ///
@@ -38,6 +53,197 @@ where
     assert_de_tokens(value, tokens);
 }
 
+/// Runs both `assert_ser_tokens_with_config` and
+/// `assert_de_tokens_with_config` with the same options.
+///
+/// ```
+/// # use serde_test::{assert_tokens_with_config, Config, Token};
+/// #
+/// let config = Config::new().int128_as_strings(true);
+/// assert_tokens_with_config(&-1i128, &[Token::Str("-1")], config);
+/// ```
+#[track_caller]
+pub fn assert_tokens_with_config<'de, T>(value: &T, tokens: &'de [Token], config: Config)
+where
+    T: Serialize + Deserialize<'de> + PartialEq + Debug,
+{
+    assert_ser_tokens_with_config(value, tokens, config.clone());
+    assert_de_tokens_with_config(value, tokens, config);
+}
+
+/// Asserts that `value` deserializes back into itself from the tokens it
+/// serializes to, and that the deserialized value serializes to the same
+/// tokens again.
+///
+/// This is for types whose exact token stream does not matter, only that
+/// their `Serialize` and `Deserialize` impls agree with each other. The
+/// second serialization catches asymmetries that a lenient `PartialEq`
+/// would hide, such as a field that compares equal but is lost on the way
+/// back.
+///
+/// Like [`to_tokens`], this interns the strings and bytes in `value`, leaking
+/// each distinct one for the rest of the process.
+///
+/// ```
+/// # use serde_derive::{Deserialize, Serialize};
+/// # use serde_test::assert_roundtrip;
+/// #
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Settings {
+///     name: String,
+///     retries: Option<u8>,
+///     tags: Vec<String>,
+/// }
+///
+/// assert_roundtrip(&Settings {
+///     name: "primary".to_owned(),
+///     retries: Some(3),
+///     tags: vec!["a".to_owned(), "b".to_owned()],
+/// });
+/// ```
+#[track_caller]
+pub fn assert_roundtrip<T>(value: &T)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let tokens = to_tokens(value);
+    assert_de_tokens(value, &tokens);
+    // A failure to deserialize has already been reported above.
+    if let Ok(deserialized) = T::deserialize(&mut Deserializer::new(&tokens)) {
+        assert_ser_tokens(&deserialized, &tokens);
+    }
+}
+
+/// Like [`assert_tokens`], but for types without `Debug`, such as types
+/// holding secrets. A failure says that the values differ without printing
+/// either of them.
+///
+/// ```
+/// # use serde_derive::{Deserialize, Serialize};
+/// # use serde_test::{assert_tokens_redacted, Token};
+/// #
+/// #[derive(Serialize, Deserialize, PartialEq)]
+/// struct ApiKey(String);
+///
+/// assert_tokens_redacted(
+///     &ApiKey("hunter2".to_owned()),
+///     &[Token::NewtypeStruct { name: "ApiKey" }, Token::Str("hunter2")],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_tokens_redacted<'de, T>(value: &T, tokens: &'de [Token])
+where
+    T: Serialize + Deserialize<'de> + PartialEq,
+{
+    assert_ser_tokens(value, tokens);
+    assert_de_checked(
+        tokens,
+        |v: &T| v == value,
+        "deserialized value differs from the expected one (values not printed)",
+    );
+}
+
+/// Like [`assert_roundtrip`], but for types without `Debug`. A failure says
+/// that the values differ without printing either of them.
+///
+/// Like [`to_tokens`], this interns the strings and bytes in `value`, leaking
+/// each distinct one for the rest of the process.
+///
+/// ```
+/// # use serde_derive::{Deserialize, Serialize};
+/// # use serde_test::assert_roundtrip_redacted;
+/// #
+/// #[derive(Serialize, Deserialize, PartialEq)]
+/// struct Credentials {
+///     user: String,
+///     password: String,
+/// }
+///
+/// assert_roundtrip_redacted(&Credentials {
+///     user: "admin".to_owned(),
+///     password: "hunter2".to_owned(),
+/// });
+/// ```
+#[track_caller]
+pub fn assert_roundtrip_redacted<T>(value: &T)
+where
+    T: Serialize + DeserializeOwned + PartialEq,
+{
+    let tokens = to_tokens(value);
+    assert_de_checked(
+        &tokens,
+        |v: &T| v == value,
+        "deserialized value differs from the original (values not printed)",
+    );
+    // A failure to deserialize has already been reported above.
+    if let Ok(deserialized) = T::deserialize(&mut Deserializer::new(&tokens)) {
+        assert_ser_tokens(&deserialized, &tokens);
+    }
+}
+
+/// Runs `assert_tokens` against the same shared `value` from `threads`
+/// threads at once, `iterations` times on each.
+///
+/// The threads are released together so that their calls overlap as much as
+/// possible, which surfaces races in `Serialize` impls over locks, caches and
+/// other interior mutability. A failure on any thread fails the assertion
+/// once every thread has finished.
+///
+/// ```
+/// # use serde_test::{assert_tokens_concurrent, Token};
+/// # use std::sync::Arc;
+/// #
+/// let value = Arc::new(vec![1u8, 2]);
+/// assert_tokens_concurrent(
+///     &value,
+///     &[
+///         Token::Seq { len: Some(2) },
+///         Token::U8(1),
+///         Token::U8(2),
+///         Token::SeqEnd,
+///     ],
+///     8,
+///     100,
+/// );
+/// ```
+#[track_caller]
+pub fn assert_tokens_concurrent<T>(
+    value: &Arc<T>,
+    tokens: &[Token],
+    threads: usize,
+    iterations: usize,
+) where
+    T: Serialize + DeserializeOwned + PartialEq + Debug + Send + Sync + 'static,
+{
+    let tokens: Arc<[Token]> = Arc::from(tokens);
+    let barrier = Arc::new(Barrier::new(threads));
+    let handles: Vec<_> = (0..threads)
+        .map(|_| {
+            let value = Arc::clone(value);
+            let tokens = Arc::clone(&tokens);
+            let barrier = Arc::clone(&barrier);
+            thread::spawn(move || {
+                barrier.wait();
+                for _ in 0..iterations {
+                    assert_tokens(&*value, &tokens);
+                }
+                take_failures()
+            })
+        })
+        .collect();
+
+    let mut panic = None;
+    for handle in handles {
+        match handle.join() {
+            Ok(failures) => record_failures(failures),
+            Err(payload) => panic = panic.or(Some(payload)),
+        }
+    }
+    if let Some(payload) = panic {
+        panic::resume_unwind(payload);
+    }
+}
+
 /// Asserts that `value` serializes to the given `tokens`.
 ///
 /// ```
@@ -63,173 +269,2001 @@ where
 ///     ],
 /// );
 /// ```
+///
+/// The value may be unsized, so string slices, slices and paths can be
+/// asserted directly rather than through a reference to a reference:
+///
+/// ```
+/// # use serde_test::{assert_ser_tokens, Token};
+/// # use std::path::Path;
+/// #
+/// assert_ser_tokens("abc", &[Token::Str("abc")]);
+/// assert_ser_tokens(Path::new("/tmp"), &[Token::Str("/tmp")]);
+/// assert_ser_tokens(
+///     &[1u8, 2][..],
+///     &[
+///         Token::Seq { len: Some(2) },
+///         Token::U8(1),
+///         Token::U8(2),
+///         Token::SeqEnd,
+///     ],
+/// );
+/// ```
 #[track_caller]
 pub fn assert_ser_tokens<T>(value: &T, tokens: &[Token])
 where
     T: ?Sized + Serialize,
 {
-    let mut ser = Serializer::new(tokens);
-    match value.serialize(&mut ser) {
-        Ok(()) => {}
-        Err(err) => panic!("value failed to serialize: {}", err),
+    assert_ser_tokens_with_config(value, tokens, Config::new());
+}
+
+/// Asserts that `value` serializes to the given `tokens` using the options in
+/// `config`, and returns a [`Report`] about the tokens that were matched.
+///
+/// ```
+/// # use serde_test::{assert_ser_tokens_with_config, Config, Token};
+/// #
+/// let config = Config::new().int128_as_strings(true);
+/// assert_ser_tokens_with_config(&-1i128, &[Token::Str("-1")], config);
+/// ```
+#[track_caller]
+// By value, like the config of every other `_with_config` assertion.
+#[allow(clippy::needless_pass_by_value)]
+pub fn assert_ser_tokens_with_config<T>(value: &T, tokens: &[Token], config: Config) -> Report
+where
+    T: ?Sized + Serialize,
+{
+    let mut ser = Serializer::with_config(tokens, config.clone());
+    if let Err(err) = value.serialize(&mut ser) {
+        let mismatch = Mismatch::from_error("value failed to serialize", &err);
+        fail(&config, tokens, mismatch);
+    } else if ser.remaining() > 0 {
+        fail(&config, tokens, Mismatch::remaining(tokens, ser.index()));
     }
+    ser.report()
+}
 
-    if ser.remaining() > 0 {
-        panic!("{} remaining tokens", ser.remaining());
+/// Asserts that `serialize_as` serializes `value` to the given `tokens`.
+///
+/// This tests a conversion adapter, such as a `serde_with::SerializeAs` impl
+/// or a module meant for `#[serde(serialize_with = "...")]`, directly against
+/// the tokens without wrapping the value in an annotated struct.
+///
+/// ```
+/// # use serde::{Serialize, Serializer};
+/// # use serde_test::{assert_ser_as, Token};
+/// #
+/// fn as_string<S>(value: &u32, serializer: S) -> Result<S::Ok, S::Error>
+/// where
+///     S: Serializer,
+/// {
+///     value.to_string().serialize(serializer)
+/// }
+///
+/// assert_ser_as(&42, &[Token::Str("42")], |value, serializer| {
+///     as_string(value, serializer)
+/// });
+/// ```
+#[track_caller]
+pub fn assert_ser_as<T, F>(value: &T, tokens: &[Token], serialize_as: F)
+where
+    T: ?Sized,
+    F: FnOnce(&T, &mut Serializer) -> Result<(), Error>,
+{
+    let config = Config::new();
+    let mut ser = Serializer::new(tokens);
+    if let Err(err) = serialize_as(value, &mut ser) {
+        let mismatch = Mismatch::from_error("value failed to serialize", &err);
+        fail(&config, tokens, mismatch);
+    } else if ser.remaining() > 0 {
+        fail(&config, tokens, Mismatch::remaining(tokens, ser.index()));
     }
 }
 
-/// Asserts that `value` serializes to the given `tokens`, and then yields
-/// `error`.
+/// Runs both `assert_ser_as` and `assert_de_as`, for the shim generated by
+/// `#[serde(remote = "...")]`.
+///
+/// A remote definition's `serialize` and `deserialize` functions are driven
+/// directly against the tokens, without wrapping the foreign type in a struct
+/// that names the definition in `#[serde(with = "...")]`.
 ///
 /// ```
-/// use serde_derive::Serialize;
-/// use serde_test::{assert_ser_tokens_error, Token};
-/// use std::sync::{Arc, Mutex};
-/// use std::thread;
+/// # use serde_derive::{Deserialize, Serialize};
+/// # use serde_test::{assert_remote_tokens, Token};
+/// # use std::time::Duration;
+/// #
+/// #[derive(Serialize, Deserialize)]
+/// #[serde(remote = "Duration")]
+/// struct DurationDef {
+///     #[serde(getter = "Duration::as_secs")]
+///     secs: u64,
+///     #[serde(getter = "Duration::subsec_nanos")]
+///     nanos: u32,
+/// }
 ///
-/// #[derive(Serialize)]
-/// struct Example {
-///     lock: Arc<Mutex<u32>>,
+/// impl From<DurationDef> for Duration {
+///     fn from(def: DurationDef) -> Duration {
+///         Duration::new(def.secs, def.nanos)
+///     }
 /// }
 ///
-/// fn main() {
-///     let example = Example {
-///         lock: Arc::new(Mutex::new(0)),
-///     };
-///     let lock = example.lock.clone();
+/// assert_remote_tokens(
+///     &Duration::new(1, 5),
+///     &[
+///         Token::Struct { name: "DurationDef", len: 2 },
+///         Token::Str("secs"),
+///         Token::U64(1),
+///         Token::Str("nanos"),
+///         Token::U32(5),
+///         Token::StructEnd,
+///     ],
+///     |value, serializer| DurationDef::serialize(value, serializer),
+///     |deserializer| DurationDef::deserialize(deserializer),
+/// );
+/// ```
+#[track_caller]
+pub fn assert_remote_tokens<'de, T, S, D>(
+    value: &T,
+    tokens: &'de [Token],
+    serialize: S,
+    deserialize: D,
+) where
+    T: PartialEq + Debug,
+    S: FnOnce(&T, &mut Serializer) -> Result<(), Error>,
+    D: FnOnce(&mut Deserializer<'de>) -> Result<T, Error>,
+{
+    assert_ser_as(value, tokens, serialize);
+    assert_de_as(value, tokens, deserialize);
+}
+
+/// Asserts that a C-like enum variant serializes as its `discriminant`, and
+/// that it deserializes from the discriminant as well as from its `name` if
+/// one is given.
 ///
-///     let thread = thread::spawn(move || {
-///         // This thread will acquire the mutex first, unwrapping the result
-///         // of `lock` because the lock has not been poisoned.
-///         let _guard = lock.lock().unwrap();
+/// The discriminant is a single numeric token such as `Token::U8(2)`. This
+/// covers the common pattern of a field-less enum with custom impls that
+/// serialize it as a number but also accept the variant name on input.
 ///
-///         // This panic while holding the lock (`_guard` is in scope) will
-///         // poison the mutex.
-///         panic!()
-///     });
-///     thread.join();
+/// ```
+/// # use serde::de::{self, Deserialize, Deserializer, Visitor};
+/// # use serde::{Serialize, Serializer};
+/// # use serde_test::{assert_discriminant, Token};
+/// # use std::fmt;
+/// #
+/// #[derive(Copy, Clone, PartialEq, Debug)]
+/// enum Level {
+///     Low = 1,
+///     High = 2,
+/// }
 ///
-///     let expected = &[
-///         Token::Struct {
-///             name: "Example",
-///             len: 1,
-///         },
-///         Token::Str("lock"),
-///     ];
-///     let error = "lock poison error while serializing";
-///     assert_ser_tokens_error(&example, expected, error);
+/// impl Serialize for Level {
+///     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+///     where
+///         S: Serializer,
+///     {
+///         serializer.serialize_u8(*self as u8)
+///     }
 /// }
+///
+/// impl<'de> Deserialize<'de> for Level {
+///     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+///     where
+///         D: Deserializer<'de>,
+///     {
+///         struct LevelVisitor;
+///
+///         impl<'de> Visitor<'de> for LevelVisitor {
+///             type Value = Level;
+///
+///             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+///                 formatter.write_str("a level")
+///             }
+///
+///             fn visit_u64<E: de::Error>(self, v: u64) -> Result<Level, E> {
+///                 match v {
+///                     1 => Ok(Level::Low),
+///                     2 => Ok(Level::High),
+///                     _ => Err(E::invalid_value(de::Unexpected::Unsigned(v), &self)),
+///                 }
+///             }
+///
+///             fn visit_str<E: de::Error>(self, v: &str) -> Result<Level, E> {
+///                 match v {
+///                     "Low" => Ok(Level::Low),
+///                     "High" => Ok(Level::High),
+///                     _ => Err(E::unknown_variant(v, &["Low", "High"])),
+///                 }
+///             }
+///         }
+///
+///         deserializer.deserialize_any(LevelVisitor)
+///     }
+/// }
+///
+/// assert_discriminant(&Level::High, Token::U8(2), Some("High"));
 /// ```
 #[track_caller]
-pub fn assert_ser_tokens_error<T>(value: &T, tokens: &[Token], error: &str)
+pub fn assert_discriminant<T>(value: &T, discriminant: Token, name: Option<&'static str>)
 where
-    T: ?Sized + Serialize,
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
 {
-    let mut ser = Serializer::new(tokens);
-    match value.serialize(&mut ser) {
-        Ok(()) => panic!("value serialized successfully"),
-        Err(e) => assert_eq!(e, *error),
+    assert_tokens(value, &[discriminant]);
+    if let Some(name) = name {
+        assert_de_tokens(value, &[Token::Str(name)]);
     }
+}
 
-    if ser.remaining() > 0 {
-        panic!("{} remaining tokens", ser.remaining());
+/// Asserts that the enum called `name` deserializes every one of the
+/// `unknown_names` and `unknown_indices` as the variant `fallback`.
+///
+/// This covers the forward compatibility that `#[serde(other)]` provides:
+/// variants added by a newer version of a format must land on the fallback
+/// variant, whether the format identifies variants by name or by index.
+/// Each unknown variant is tried as a unit variant.
+///
+/// ```
+/// # use serde_derive::Deserialize;
+/// # use serde_test::assert_de_other_variant;
+/// #
+/// #[derive(Deserialize, PartialEq, Debug)]
+/// enum Shape {
+///     Circle,
+///     Square,
+///     #[serde(other)]
+///     Unknown,
+/// }
+///
+/// assert_de_other_variant(&Shape::Unknown, "Shape", &["Triangle", "circle", ""], 3..100);
+/// ```
+#[track_caller]
+pub fn assert_de_other_variant<T>(
+    fallback: &T,
+    name: &'static str,
+    unknown_names: &[&'static str],
+    unknown_indices: Range<u32>,
+) where
+    T: DeserializeOwned + PartialEq + Debug,
+{
+    for &variant in unknown_names {
+        assert_de_tokens(fallback, &[Token::UnitVariant { name, variant }]);
+    }
+    for index in unknown_indices {
+        assert_de_tokens(
+            fallback,
+            &[Token::Enum { name }, Token::U32(index), Token::Unit],
+        );
     }
 }
 
-/// Asserts that the given `tokens` deserialize into `value`.
+/// Asserts that the map or struct `tokens` of an internally tagged enum
+/// deserialize into `value` whichever position the `tag` entry is moved to.
+///
+/// `tokens` are the stream as serialized, with the tag entry first. The
+/// assertion moves that entry to every other position in the map in turn,
+/// including last, and checks each stream. Producers outside of serde do not
+/// promise to write the tag first, and an impl that relies on it fails here.
 ///
 /// ```
-/// # use serde_derive::{Deserialize, Serialize};
-/// # use serde_test::{assert_de_tokens, Token};
+/// # use serde_derive::Deserialize;
+/// # use serde_test::{assert_de_tag_positions, Token};
 /// #
-/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
-/// struct S {
-///     a: u8,
-///     b: u8,
+/// #[derive(Deserialize, PartialEq, Debug)]
+/// #[serde(tag = "type")]
+/// enum Message {
+///     Request { id: u32, method: String },
 /// }
 ///
-/// let s = S { a: 0, b: 0 };
-/// assert_de_tokens(
-///     &s,
+/// assert_de_tag_positions(
+///     &Message::Request {
+///         id: 1,
+///         method: "ping".to_owned(),
+///     },
+///     "type",
 ///     &[
-///         Token::Struct { name: "S", len: 2 },
-///         Token::Str("a"),
-///         Token::U8(0),
-///         Token::Str("b"),
-///         Token::U8(0),
+///         Token::Struct { name: "Message", len: 3 },
+///         Token::Str("type"),
+///         Token::Str("Request"),
+///         Token::Str("id"),
+///         Token::U32(1),
+///         Token::Str("method"),
+///         Token::Str("ping"),
 ///         Token::StructEnd,
 ///     ],
 /// );
 /// ```
 #[track_caller]
-pub fn assert_de_tokens<'de, T>(value: &T, tokens: &'de [Token])
+pub fn assert_de_tag_positions<T>(value: &T, tag: &str, tokens: &[Token])
 where
-    T: Deserialize<'de> + PartialEq + Debug,
+    T: DeserializeOwned + PartialEq + Debug,
 {
-    let mut de = Deserializer::new(tokens);
-    let mut deserialized_val = match T::deserialize(&mut de) {
-        Ok(v) => {
-            assert_eq!(v, *value);
-            v
-        }
-        Err(e) => panic!("tokens failed to deserialize: {}", e),
+    let (open, body, close) = match tokens {
+        [open @ Token::Struct { .. }, body @ .., close @ Token::StructEnd]
+        | [open @ Token::Map { .. }, body @ .., close @ Token::MapEnd] => (open, body, close),
+        _ => panic!("expected the tokens of a map or struct"),
     };
-    if de.remaining() > 0 {
-        panic!("{} remaining tokens", de.remaining());
+
+    let mut entries = Vec::new();
+    let mut rest = body;
+    while !rest.is_empty() {
+        let key = value_len(rest);
+        let len = key + value_len(&rest[key..]);
+        entries.push(&rest[..len]);
+        rest = &rest[len..];
     }
+    let tag_index = entries
+        .iter()
+        .position(|entry| match entry.first() {
+            Some(Token::Str(key) | Token::BorrowedStr(key) | Token::String(key)) => *key == tag,
+            _ => false,
+        })
+        .unwrap_or_else(|| panic!("the tokens have no `{}` entry", tag));
+    let tag_entry = entries.remove(tag_index);
 
-    // Do the same thing for deserialize_in_place. This isn't *great* because a
-    // no-op impl of deserialize_in_place can technically succeed here. Still,
-    // this should catch a lot of junk.
-    let mut de = Deserializer::new(tokens);
-    match T::deserialize_in_place(&mut de, &mut deserialized_val) {
-        Ok(()) => {
-            assert_eq!(deserialized_val, *value);
+    for position in 0..=entries.len() {
+        let mut moved = entries.clone();
+        moved.insert(position, tag_entry);
+        let mut tokens = vec![*open];
+        for entry in moved {
+            tokens.extend_from_slice(entry);
         }
-        Err(e) => panic!("tokens failed to deserialize_in_place: {}", e),
-    }
-    if de.remaining() > 0 {
-        panic!("{} remaining tokens", de.remaining());
+        tokens.push(*close);
+        assert_de_tokens(value, &tokens);
     }
 }
 
-/// Asserts that the given `tokens` yield `error` when deserializing.
+/// Asserts that the struct `tokens` deserialize into `value`, and that the
+/// same fields written positionally as a sequence do too.
+///
+/// Structs derived with `serde_derive` accept both a map of fields, which is
+/// what self-describing formats produce, and a sequence of field values in
+/// declaration order, which is what compact formats such as bincode rely
+/// on. The sequence form is built from `tokens` by dropping the keys, so the
+/// fields in `tokens` must be listed in declaration order.
 ///
 /// ```
-/// # use serde_derive::{Deserialize, Serialize};
-/// # use serde_test::{assert_de_tokens_error, Token};
+/// # use serde_derive::Deserialize;
+/// # use serde_test::{assert_de_struct_both_forms, Token};
 /// #
-/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
-/// #[serde(deny_unknown_fields)]
-/// struct S {
-///     a: u8,
-///     b: u8,
+/// #[derive(Deserialize, PartialEq, Debug)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
 /// }
 ///
-/// assert_de_tokens_error::<S>(
+/// assert_de_struct_both_forms(
+///     &Point { x: 1, y: 2 },
 ///     &[
-///         Token::Struct { name: "S", len: 2 },
+///         Token::Struct { name: "Point", len: 2 },
 ///         Token::Str("x"),
+///         Token::I32(1),
+///         Token::Str("y"),
+///         Token::I32(2),
+///         Token::StructEnd,
 ///     ],
-///     "unknown field `x`, expected `a` or `b`",
 /// );
 /// ```
 #[track_caller]
-pub fn assert_de_tokens_error<'de, T>(tokens: &'de [Token], error: &str)
+pub fn assert_de_struct_both_forms<T>(value: &T, tokens: &[Token])
 where
-    T: Deserialize<'de>,
+    T: DeserializeOwned + PartialEq + Debug,
 {
-    let mut de = Deserializer::new(tokens);
-    match T::deserialize(&mut de) {
-        Ok(_) => panic!("tokens deserialized successfully"),
-        Err(e) => assert_eq!(e, *error),
+    let body = match tokens {
+        [Token::Struct { .. }, body @ .., Token::StructEnd]
+        | [Token::Map { .. }, body @ .., Token::MapEnd] => body,
+        _ => panic!("expected the tokens of a map or struct"),
+    };
+    assert_de_tokens(value, tokens);
+
+    let mut fields = Vec::new();
+    let mut rest = body;
+    while !rest.is_empty() {
+        let key = value_len(rest);
+        let len = key + value_len(&rest[key..]);
+        fields.push(&rest[key..len]);
+        rest = &rest[len..];
+    }
+    let mut seq = vec![Token::Seq {
+        len: Some(fields.len()),
+    }];
+    for field in fields {
+        seq.extend_from_slice(field);
     }
+    seq.push(Token::SeqEnd);
+    assert_de_tokens(value, &seq);
+}
 
-    // There may be one token left if a peek caused the error
-    de.next_token_opt();
+/// The ways a byte-array-like value can be serialized.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ByteRepr {
+    /// A single call to `serialize_bytes`, as with `serde_bytes`.
+    Bytes,
+    /// A tuple of `u8`, as with a plain `[u8; N]`.
+    Tuple,
+    /// A sequence of `u8`, as with a plain `Vec<u8>` or `&[u8]`.
+    Seq,
+}
 
-    if de.remaining() > 0 {
-        panic!("{} remaining tokens", de.remaining());
+/// Asserts that `value` serializes as the given `bytes` using one of the
+/// `accepted` representations.
+///
+/// Whether a byte array goes through `serialize_bytes` or element by element
+/// depends on details like a `serde_bytes` annotation, and switching between
+/// them silently changes the format of binary encodings.
+///
+/// ```
+/// # use serde_test::{assert_ser_bytes_repr, ByteRepr};
+/// #
+/// assert_ser_bytes_repr(&[1u8, 2, 3], b"\x01\x02\x03", &[ByteRepr::Tuple]);
+/// assert_ser_bytes_repr(&vec![1u8, 2, 3], b"\x01\x02\x03", &[ByteRepr::Bytes, ByteRepr::Seq]);
+/// ```
+#[track_caller]
+pub fn assert_ser_bytes_repr<T>(value: &T, bytes: &'static [u8], accepted: &[ByteRepr])
+where
+    T: ?Sized + Serialize,
+{
+    let len = bytes.len();
+    let elements = bytes.iter().map(|&b| Token::U8(b));
+    let candidates = [
+        (ByteRepr::Bytes, vec![Token::Bytes(bytes)]),
+        (
+            ByteRepr::Tuple,
+            iter::once(Token::Tuple { len })
+                .chain(elements.clone())
+                .chain(iter::once(Token::TupleEnd))
+                .collect(),
+        ),
+        (
+            ByteRepr::Seq,
+            iter::once(Token::Seq { len: Some(len) })
+                .chain(elements)
+                .chain(iter::once(Token::SeqEnd))
+                .collect(),
+        ),
+    ];
+
+    let actual = candidates.iter().find(|(_, tokens)| {
+        let mut ser = Serializer::new(tokens);
+        value.serialize(&mut ser).is_ok() && ser.remaining() == 0
+    });
+    let mismatch = match actual {
+        Some((repr, _)) if accepted.contains(repr) => return,
+        Some((repr, _)) => Mismatch::new(format!(
+            "value serialized as {:?} but the accepted representations are {:?}",
+            repr, accepted,
+        )),
+        None => Mismatch::new(format!(
+            "value did not serialize as the bytes {:?} in any representation",
+            bytes,
+        )),
+    };
+    fail(&Config::new(), &[], mismatch);
+}
+
+/// Runs both `assert_ser_tokens` and `assert_de_tokens` with the serializer
+/// and deserializer reporting themselves as human-readable.
+///
+/// This is the same as calling [`assert_tokens`] on `value.readable()`, but
+/// without having to clone `value` to wrap it.
+///
+/// ```
+/// # use serde_test::{assert_tokens_compact, assert_tokens_readable, Token};
+/// # use std::net::Ipv4Addr;
+/// #
+/// let addr = Ipv4Addr::new(127, 0, 0, 1);
+/// assert_tokens_readable(&addr, &[Token::Str("127.0.0.1")]);
+/// assert_tokens_compact(
+///     &addr,
+///     &[
+///         Token::Tuple { len: 4 },
+///         Token::U8(127),
+///         Token::U8(0),
+///         Token::U8(0),
+///         Token::U8(1),
+///         Token::TupleEnd,
+///     ],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_tokens_readable<'de, T>(value: &T, tokens: &'de [Token])
+where
+    T: Serialize + Deserialize<'de> + PartialEq + Debug,
+{
+    assert_tokens_with_readability(value, tokens, true);
+}
+
+/// Runs both `assert_ser_tokens` and `assert_de_tokens` with the serializer
+/// and deserializer reporting themselves as compact.
+///
+/// This is the same as calling [`assert_tokens`] on `value.compact()`, but
+/// without having to clone `value` to wrap it.
+#[track_caller]
+pub fn assert_tokens_compact<'de, T>(value: &T, tokens: &'de [Token])
+where
+    T: Serialize + Deserialize<'de> + PartialEq + Debug,
+{
+    assert_tokens_with_readability(value, tokens, false);
+}
+
+/// Runs [`assert_tokens_readable`] against the `readable` tokens and
+/// [`assert_tokens_compact`] against the `compact` tokens.
+///
+/// Types that branch on `is_human_readable` need both checks, and this keeps
+/// them together in one call.
+///
+/// ```
+/// # use serde_test::{assert_tokens_all, Token};
+/// # use std::net::Ipv4Addr;
+/// #
+/// assert_tokens_all(
+///     &Ipv4Addr::new(10, 0, 0, 1),
+///     &[Token::Str("10.0.0.1")],
+///     &[
+///         Token::Tuple { len: 4 },
+///         Token::U8(10),
+///         Token::U8(0),
+///         Token::U8(0),
+///         Token::U8(1),
+///         Token::TupleEnd,
+///     ],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_tokens_all<'de, T>(value: &T, readable: &'de [Token], compact: &'de [Token])
+where
+    T: Serialize + Deserialize<'de> + PartialEq + Debug,
+{
+    assert_tokens_with_readability(value, readable, true);
+    assert_tokens_with_readability(value, compact, false);
+}
+
+#[track_caller]
+fn assert_tokens_with_readability<'de, T>(value: &T, tokens: &'de [Token], readable: bool)
+where
+    T: Serialize + Deserialize<'de> + PartialEq + Debug,
+{
+    assert_tokens_with_config(value, tokens, Config::new().human_readable(readable));
+}
+
+/// Asserts that `value` serializes to the `readable` tokens in human-readable
+/// mode and to the `compact` tokens in compact mode, and that the two differ.
+///
+/// This guards against an impl in which one branch of its
+/// `is_human_readable` check was accidentally deleted, leaving both modes
+/// identical.
+///
+/// ```
+/// # use serde::{Serialize, Serializer};
+/// # use serde_test::{assert_readable_ne_compact, Token};
+/// #
+/// struct Version(u8, u8);
+///
+/// impl Serialize for Version {
+///     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+///     where
+///         S: Serializer,
+///     {
+///         if serializer.is_human_readable() {
+///             format!("{}.{}", self.0, self.1).serialize(serializer)
+///         } else {
+///             (self.0, self.1).serialize(serializer)
+///         }
+///     }
+/// }
+///
+/// assert_readable_ne_compact(
+///     &Version(1, 0),
+///     &[Token::Str("1.0")],
+///     &[
+///         Token::Tuple { len: 2 },
+///         Token::U8(1),
+///         Token::U8(0),
+///         Token::TupleEnd,
+///     ],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_readable_ne_compact<T>(value: &T, readable: &[Token], compact: &[Token])
+where
+    T: ?Sized + Serialize,
+{
+    if readable == compact {
+        let mismatch = Mismatch::new(
+            "readable and compact token streams are identical, expected them to differ".to_owned(),
+        );
+        return fail(&Config::new(), readable, mismatch);
+    }
+    assert_ser_tokens(&value.readable(), readable);
+    assert_ser_tokens(&value.compact(), compact);
+}
+
+/// Asserts that serializing `value` makes exactly the `Serializer` calls
+/// described by `trace`.
+///
+/// The trace has one call per line, including its arguments, with the calls
+/// made on behalf of a compound value or a nested value indented underneath
+/// the call that started it. Unlike a token stream, the trace distinguishes
+/// `serialize_entry` from separate `serialize_key` and `serialize_value`
+/// calls, shows skipped fields and `collect_str`, and records every length
+/// hint. Indentation common to all lines of `trace` is ignored so it can be
+/// written as an indented string literal.
+///
+/// ```
+/// # use serde_derive::Serialize;
+/// # use serde_test::assert_ser_trace;
+/// #
+/// #[derive(Serialize)]
+/// struct S {
+///     a: u8,
+///     #[serde(skip_serializing_if = "Option::is_none")]
+///     b: Option<u8>,
+/// }
+///
+/// assert_ser_trace(
+///     &S { a: 0, b: None },
+///     r#"
+///         serialize_struct("S", 1)
+///           serialize_field("a")
+///             serialize_u8(0)
+///           skip_field("b")
+///           end
+///     "#,
+/// );
+/// ```
+#[track_caller]
+pub fn assert_ser_trace<T>(value: &T, trace: &str)
+where
+    T: ?Sized + Serialize,
+{
+    let actual = ser_trace(value);
+    let expected = dedent(trace);
+    if actual != expected {
+        let mismatch = Mismatch::new(format!(
+            "serializer calls did not match the expected trace\n\
+             expected:\n{}\nactual:\n{}",
+            expected, actual,
+        ));
+        fail(&Config::new(), &[], mismatch);
+    }
+}
+
+/// Asserts that `value` serializes to the given `tokens` while keeping at most
+/// `max_bytes` of memory allocated at any one time.
+///
+/// Requires [`TrackingAllocator`] to be installed as the global allocator.
+///
+/// [`TrackingAllocator`]: crate::TrackingAllocator
+///
+/// ```
+/// # use serde_test::{assert_ser_tokens_peak_memory, Token, TrackingAllocator};
+/// #
+/// #[global_allocator]
+/// static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+///
+/// # fn main() {
+/// let vec = vec!['a', 'b'];
+/// assert_ser_tokens_peak_memory(
+///     &vec,
+///     &[
+///         Token::Seq { len: Some(2) },
+///         Token::Char('a'),
+///         Token::Char('b'),
+///         Token::SeqEnd,
+///     ],
+///     0,
+/// );
+/// # }
+/// ```
+#[track_caller]
+pub fn assert_ser_tokens_peak_memory<T>(value: &T, tokens: &[Token], max_bytes: usize)
+where
+    T: ?Sized + Serialize,
+{
+    let config = Config::new();
+    let mut ser = Serializer::new(tokens);
+    let (result, peak) = peak_memory(|| value.serialize(&mut ser));
+    if let Err(err) = result {
+        let mismatch = Mismatch::from_error("value failed to serialize", &err);
+        return fail(&config, tokens, mismatch);
+    }
+    if ser.remaining() > 0 {
+        return fail(&config, tokens, Mismatch::remaining(tokens, ser.index()));
+    }
+    if peak > max_bytes {
+        let mismatch = Mismatch::new(format!(
+            "serialization had {} bytes live at peak, more than the limit of {}",
+            peak, max_bytes,
+        ));
+        fail(&config, tokens, mismatch);
+    }
+}
+
+/// Asserts that serializing `value` twice produces the same tokens.
+///
+/// This surfaces nondeterminism in a `Serialize` impl, such as output that
+/// depends on interior mutability, a cache, a counter or a random salt. The
+/// failure points at the first token that differs between the two
+/// serializations.
+///
+/// Like [`to_tokens`], this interns the strings and bytes in `value`, leaking
+/// each distinct one for the rest of the process.
+///
+/// ```should_panic
+/// # use serde::{Serialize, Serializer};
+/// # use serde_test::assert_ser_deterministic;
+/// # use std::cell::Cell;
+/// #
+/// struct Counter(Cell<u32>);
+///
+/// impl Serialize for Counter {
+///     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+///     where
+///         S: Serializer,
+///     {
+///         self.0.set(self.0.get() + 1);
+///         serializer.serialize_u32(self.0.get())
+///     }
+/// }
+///
+/// assert_ser_deterministic(&Counter(Cell::new(0)));
+/// ```
+#[track_caller]
+pub fn assert_ser_deterministic<T>(value: &T)
+where
+    T: ?Sized + Serialize,
+{
+    let first = to_tokens(value);
+    let second = to_tokens(value);
+    if let Err(mismatch) = compare_tokens(&first, &second) {
+        fail(&Config::new(), &first, mismatch);
+    }
+}
+
+/// Asserts that `value` serializes to at most `max_tokens` tokens.
+///
+/// This catches a change that makes a type noticeably bigger on the wire,
+/// such as a new field or a switch from a compact representation, without
+/// spelling out the whole stream.
+///
+/// Like [`to_tokens`], this interns the strings and bytes in `value`, leaking
+/// each distinct one for the rest of the process.
+///
+/// ```
+/// # use serde_derive::Serialize;
+/// # use serde_test::assert_ser_max_tokens;
+/// #
+/// #[derive(Serialize)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// // Struct, 2 keys, 2 values, StructEnd.
+/// assert_ser_max_tokens(&Point { x: 1, y: 2 }, 6);
+/// ```
+#[track_caller]
+pub fn assert_ser_max_tokens<T>(value: &T, max_tokens: usize)
+where
+    T: ?Sized + Serialize,
+{
+    let tokens = to_tokens(value);
+    if tokens.len() > max_tokens {
+        let mismatch = Mismatch::new(format!(
+            "value serialized to {} tokens, more than the limit of {}",
+            tokens.len(),
+            max_tokens,
+        ));
+        fail(&Config::new(), &tokens, mismatch);
+    }
+}
+
+/// Asserts that the strings and bytes `value` serializes take at most
+/// `max_bytes` bytes in total, counting every key, value and name written as
+/// a string token.
+///
+/// Like [`to_tokens`], this interns the strings and bytes in `value`, leaking
+/// each distinct one for the rest of the process.
+///
+/// ```
+/// # use serde_test::assert_ser_max_payload_bytes;
+/// # use std::collections::BTreeMap;
+/// #
+/// let mut headers = BTreeMap::new();
+/// headers.insert("accept", "text/plain");
+///
+/// // "accept" and "text/plain"
+/// assert_ser_max_payload_bytes(&headers, 16);
+/// ```
+#[track_caller]
+pub fn assert_ser_max_payload_bytes<T>(value: &T, max_bytes: usize)
+where
+    T: ?Sized + Serialize,
+{
+    let tokens = to_tokens(value);
+    let bytes: usize = tokens.iter().map(payload_len).sum();
+    if bytes > max_bytes {
+        let mismatch = Mismatch::new(format!(
+            "value serialized {} bytes of strings and bytes, more than the limit of {}",
+            bytes, max_bytes,
+        ));
+        fail(&Config::new(), &tokens, mismatch);
+    }
+}
+
+fn payload_len(token: &Token) -> usize {
+    match *token {
+        Token::Str(s) | Token::BorrowedStr(s) | Token::String(s) => s.len(),
+        Token::Bytes(b) | Token::BorrowedBytes(b) | Token::ByteBuf(b) => b.len(),
+        _ => 0,
+    }
+}
+
+/// Asserts that `value` serializes to the given `tokens`, and then yields
+/// `error`.
+///
+/// ```
+/// use serde_derive::Serialize;
+/// use serde_test::{assert_ser_tokens_error, Token};
+/// use std::sync::{Arc, Mutex};
+/// use std::thread;
+///
+/// #[derive(Serialize)]
+/// struct Example {
+///     lock: Arc<Mutex<u32>>,
+/// }
+///
+/// fn main() {
+///     let example = Example {
+///         lock: Arc::new(Mutex::new(0)),
+///     };
+///     let lock = example.lock.clone();
+///
+///     let thread = thread::spawn(move || {
+///         // This thread will acquire the mutex first, unwrapping the result
+///         // of `lock` because the lock has not been poisoned.
+///         let _guard = lock.lock().unwrap();
+///
+///         // This panic while holding the lock (`_guard` is in scope) will
+///         // poison the mutex.
+///         panic!()
+///     });
+///     thread.join();
+///
+///     let expected = &[
+///         Token::Struct {
+///             name: "Example",
+///             len: 1,
+///         },
+///         Token::Str("lock"),
+///     ];
+///     let error = "lock poison error while serializing";
+///     assert_ser_tokens_error(&example, expected, error);
+/// }
+/// ```
+#[track_caller]
+pub fn assert_ser_tokens_error<T>(value: &T, tokens: &[Token], error: &str)
+where
+    T: ?Sized + Serialize,
+{
+    assert_ser_error_matches(value, tokens, error, |e| *e == *error);
+}
+
+/// Like [`assert_ser_tokens_error`], but only requires the error message to
+/// contain `fragment`, so that the test survives rewording elsewhere in the
+/// message.
+///
+/// ```
+/// # use serde_test::{assert_ser_tokens_error_contains, Token};
+/// # use std::path::Path;
+/// # use std::ffi::OsStr;
+/// # #[cfg(unix)]
+/// # {
+/// # use std::os::unix::ffi::OsStrExt;
+/// let path = Path::new(OsStr::from_bytes(b"\xFF"));
+/// assert_ser_tokens_error_contains(&path, &[], "valid UTF-8");
+/// # }
+/// ```
+#[track_caller]
+pub fn assert_ser_tokens_error_contains<T>(value: &T, tokens: &[Token], fragment: &str)
+where
+    T: ?Sized + Serialize,
+{
+    let describe = format!("an error containing {:?}", fragment);
+    assert_ser_error_matches(value, tokens, &describe, |e| {
+        e.to_string().contains(fragment)
+    });
+}
+
+/// Asserts that serializing `value` against the given `tokens` fails with an
+/// error accepted by `check`.
+///
+/// This allows richer checks than comparing the message, such as on the
+/// error's [`kind`] or [`path`].
+///
+/// [`kind`]: Error::kind
+/// [`path`]: Error::path
+///
+/// ```
+/// # use serde::ser::{Error as _, Serialize, Serializer};
+/// # use serde_test::{assert_ser_tokens_error_with, ErrorKind, Token};
+/// #
+/// struct Port(u32);
+///
+/// impl Serialize for Port {
+///     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+///     where
+///         S: Serializer,
+///     {
+///         if self.0 > 65535 {
+///             return Err(S::Error::custom(format_args!("port {} out of range", self.0)));
+///         }
+///         serializer.serialize_u16(self.0 as u16)
+///     }
+/// }
+///
+/// let ports = vec![Port(80), Port(70000)];
+/// let tokens = [Token::Seq { len: Some(2) }, Token::U16(80)];
+/// assert_ser_tokens_error_with(&ports, &tokens, |e| {
+///     e.kind() == ErrorKind::Custom && e.path() == Some("[1]")
+/// });
+/// ```
+#[track_caller]
+pub fn assert_ser_tokens_error_with<T, F>(value: &T, tokens: &[Token], check: F)
+where
+    T: ?Sized + Serialize,
+    F: FnOnce(&Error) -> bool,
+{
+    assert_ser_error_matches(value, tokens, "an error accepted by the check", check);
+}
+
+#[track_caller]
+fn assert_ser_error_matches<T, F>(value: &T, tokens: &[Token], describe: &str, matches: F)
+where
+    T: ?Sized + Serialize,
+    F: FnOnce(&Error) -> bool,
+{
+    let config = Config::new();
+    let mut ser = Serializer::new(tokens);
+    match value.serialize(&mut ser) {
+        Ok(()) => {
+            let mismatch = Mismatch::new("value serialized successfully".to_owned());
+            return fail(&config, tokens, mismatch);
+        }
+        Err(e) => {
+            if !matches(&e) {
+                return fail(&config, tokens, Mismatch::values(&e.to_string(), describe));
+            }
+        }
+    }
+
+    if ser.remaining() > 0 {
+        fail(&config, tokens, Mismatch::remaining(tokens, ser.index()));
+    }
+}
+
+/// Asserts that the given `tokens` deserialize into `value`.
+///
+/// ```
+/// # use serde_derive::{Deserialize, Serialize};
+/// # use serde_test::{assert_de_tokens, Token};
+/// #
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct S {
+///     a: u8,
+///     b: u8,
+/// }
+///
+/// let s = S { a: 0, b: 0 };
+/// assert_de_tokens(
+///     &s,
+///     &[
+///         Token::Struct { name: "S", len: 2 },
+///         Token::Str("a"),
+///         Token::U8(0),
+///         Token::Str("b"),
+///         Token::U8(0),
+///         Token::StructEnd,
+///     ],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_tokens<'de, T>(value: &T, tokens: &'de [Token])
+where
+    T: Deserialize<'de> + PartialEq + Debug,
+{
+    assert_de_tokens_with_config(value, tokens, Config::new());
+}
+
+/// Asserts that the given `tokens` deserialize into a value accepted by
+/// `check`.
+///
+/// This is for types that can't be compared with `PartialEq`, or whose
+/// comparison needs a tolerance, and for types without `Debug`. The value is
+/// not printed on failure.
+///
+/// ```
+/// # use serde_test::{assert_de_tokens_with, Token};
+/// #
+/// assert_de_tokens_with::<(f64, f64), _>(
+///     &[Token::Tuple { len: 2 }, Token::F64(0.1), Token::F64(0.2), Token::TupleEnd],
+///     |&(a, b)| (a + b - 0.3).abs() < 1e-9,
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_with<'de, T, F>(tokens: &'de [Token], check: F)
+where
+    T: Deserialize<'de>,
+    F: FnOnce(&T) -> bool,
+{
+    assert_de_checked(
+        tokens,
+        check,
+        "deserialized value was rejected by the check",
+    );
+}
+
+#[track_caller]
+fn assert_de_checked<'de, T, F>(tokens: &'de [Token], check: F, rejected: &str)
+where
+    T: Deserialize<'de>,
+    F: FnOnce(&T) -> bool,
+{
+    assert_de_result(
+        tokens,
+        "deserialize",
+        |de| T::deserialize(de),
+        |v| {
+            if check(v) {
+                Ok(())
+            } else {
+                Err(Mismatch::new(rejected.to_owned()))
+            }
+        },
+    );
+}
+
+/// Asserts that `Deserialize::deserialize_in_place` from the given `tokens`
+/// turns `place` into `expected`.
+///
+/// The starting contents of `place` are up to the caller, which allows
+/// testing that a handwritten `deserialize_in_place` overwrites or reuses
+/// existing state correctly. `place` is left holding whatever the impl made
+/// of it.
+///
+/// ```
+/// # use serde_test::{assert_de_in_place_tokens, Token};
+/// #
+/// let mut place = vec![7u8, 8, 9];
+/// assert_de_in_place_tokens(
+///     &mut place,
+///     &[Token::Seq { len: Some(2) }, Token::U8(1), Token::U8(2), Token::SeqEnd],
+///     &vec![1, 2],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_in_place_tokens<'de, T>(place: &mut T, tokens: &'de [Token], expected: &T)
+where
+    T: Deserialize<'de> + PartialEq + Debug,
+{
+    assert_de_result(
+        tokens,
+        "deserialize_in_place",
+        |de| {
+            T::deserialize_in_place(de, place)?;
+            Ok(place)
+        },
+        |place| check_value(&**place, expected),
+    );
+}
+
+/// Asserts that driving `seed` against the given `tokens` produces
+/// `expected`.
+///
+/// This tests stateful deserialization through a `DeserializeSeed`, whose
+/// state can't be supplied to a plain `Deserialize` impl.
+///
+/// ```
+/// # use serde::de::{Deserialize, DeserializeSeed, Deserializer};
+/// # use serde_test::{assert_de_seed_tokens, Token};
+/// #
+/// // Deserializes a number and adds the offset it was seeded with.
+/// struct Offset(u32);
+///
+/// impl<'de> DeserializeSeed<'de> for Offset {
+///     type Value = u32;
+///
+///     fn deserialize<D>(self, deserializer: D) -> Result<u32, D::Error>
+///     where
+///         D: Deserializer<'de>,
+///     {
+///         Ok(self.0 + u32::deserialize(deserializer)?)
+///     }
+/// }
+///
+/// assert_de_seed_tokens(Offset(100), &[Token::U32(5)], &105);
+/// ```
+#[track_caller]
+pub fn assert_de_seed_tokens<'de, S>(seed: S, tokens: &'de [Token], expected: &S::Value)
+where
+    S: DeserializeSeed<'de>,
+    S::Value: PartialEq + Debug,
+{
+    assert_de_result(
+        tokens,
+        "deserialize",
+        |de| seed.deserialize(de),
+        |v| check_value(v, expected),
+    );
+}
+
+/// Asserts that the given `tokens` deserialize into `value` using the options
+/// in `config`, and returns a [`Report`] about the tokens that were consumed.
+///
+/// ```
+/// # use serde_test::{assert_de_tokens_with_config, Config, Token};
+/// #
+/// let config = Config::new().large_integers_as_strings(true);
+/// assert_de_tokens_with_config(
+///     &"-9007199254740993".to_owned(),
+///     &[Token::I64(-9007199254740993)],
+///     config,
+/// );
+/// ```
+#[track_caller]
+// By value, like the config of every other `_with_config` assertion.
+#[allow(clippy::needless_pass_by_value)]
+pub fn assert_de_tokens_with_config<'de, T>(
+    value: &T,
+    tokens: &'de [Token],
+    config: Config,
+) -> Report
+where
+    T: Deserialize<'de> + PartialEq + Debug,
+{
+    assert_de_tokens_using(value, tokens, &config, || {
+        Deserializer::with_config(tokens, config.clone())
+    })
+}
+
+/// Asserts that `deserialize_as` deserializes the given `tokens` into
+/// `value`.
+///
+/// This is the counterpart of [`assert_ser_as`] for adapters such as a
+/// `serde_with::DeserializeAs` impl or a `deserialize_with` function.
+///
+/// ```
+/// # use serde::{de, Deserialize, Deserializer};
+/// # use serde_test::{assert_de_as, Token};
+/// #
+/// fn from_string<'de, D>(deserializer: D) -> Result<u32, D::Error>
+/// where
+///     D: Deserializer<'de>,
+/// {
+///     let s = <&str>::deserialize(deserializer)?;
+///     s.parse().map_err(de::Error::custom)
+/// }
+///
+/// assert_de_as(&42, &[Token::BorrowedStr("42")], |deserializer| {
+///     from_string(deserializer)
+/// });
+/// ```
+#[track_caller]
+pub fn assert_de_as<'de, T, F>(value: &T, tokens: &'de [Token], deserialize_as: F)
+where
+    T: PartialEq + Debug,
+    F: FnOnce(&mut Deserializer<'de>) -> Result<T, Error>,
+{
+    assert_de_result(tokens, "deserialize", deserialize_as, |v| {
+        check_value(v, value)
+    });
+}
+
+/// Asserts that the given `tokens` deserialize into a value whose `Debug`
+/// representation is the same as that of `value`.
+///
+/// This is an escape hatch for types that implement `Debug` but not
+/// `PartialEq`.
+///
+/// ```
+/// # use serde_derive::Deserialize;
+/// # use serde_test::{assert_de_tokens_debug, Token};
+/// #
+/// #[derive(Deserialize, Debug)]
+/// struct Handle {
+///     id: u8,
+/// }
+///
+/// assert_de_tokens_debug(
+///     &Handle { id: 1 },
+///     &[
+///         Token::Struct { name: "Handle", len: 1 },
+///         Token::Str("id"),
+///         Token::U8(1),
+///         Token::StructEnd,
+///     ],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_debug<'de, T>(value: &T, tokens: &'de [Token])
+where
+    T: Deserialize<'de> + Debug,
+{
+    assert_de_result(
+        tokens,
+        "deserialize",
+        |de| T::deserialize(de),
+        |v| {
+            if format!("{:?}", v) == format!("{:?}", value) {
+                Ok(())
+            } else {
+                Err(Mismatch::values(v, value))
+            }
+        },
+    );
+}
+
+/// Asserts that the given `tokens` deserialize into `value` both through the
+/// type hints the `Deserialize` impl gives and through `deserialize_any`.
+///
+/// The second pass behaves like [`Config::self_describing`]. Types meant to
+/// work with both hint-driven formats and self-describing ones are otherwise
+/// only tested on whichever path their impl happens to take.
+///
+/// ```
+/// # use serde_derive::Deserialize;
+/// # use serde_test::{assert_de_tokens_dual, Token};
+/// #
+/// #[derive(Deserialize, PartialEq, Debug)]
+/// struct S {
+///     a: u8,
+/// }
+///
+/// assert_de_tokens_dual(
+///     &S { a: 0 },
+///     &[
+///         Token::Struct { name: "S", len: 1 },
+///         Token::Str("a"),
+///         Token::U8(0),
+///         Token::StructEnd,
+///     ],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_dual<'de, T>(value: &T, tokens: &'de [Token])
+where
+    T: Deserialize<'de> + PartialEq + Debug,
+{
+    assert_de_tokens_with_config(value, tokens, Config::new());
+    assert_de_tokens_with_config(value, tokens, Config::new().self_describing(true));
+}
+
+/// Asserts that the given `tokens` deserialize into `value`, and still do
+/// when an unknown field is added to any one of the structs among them.
+///
+/// Each `Token::Struct` and `Token::StructVariant` in turn gets an extra
+/// field at the front, whose value nests a sequence, a map and a few
+/// primitives so that the impl has to skip it through
+/// `deserialize_ignored_any`. This tests that a type tolerates fields added
+/// by a newer version of its producer, without hand-writing the altered
+/// streams.
+///
+/// Maps are left alone, since an extra entry in a map field would show up in
+/// the deserialized value. A stream with no struct at all fails the
+/// assertion, since there would be nothing to test.
+///
+/// ```
+/// # use serde_derive::Deserialize;
+/// # use serde_test::{assert_de_tokens_ignore, Token};
+/// #
+/// #[derive(Deserialize, PartialEq, Debug)]
+/// struct S {
+///     a: u8,
+/// }
+///
+/// assert_de_tokens_ignore(
+///     &S { a: 1 },
+///     &[
+///         Token::Struct { name: "S", len: 1 },
+///         Token::Str("a"),
+///         Token::U8(1),
+///         Token::StructEnd,
+///     ],
+/// );
+/// ```
+///
+/// A struct with `#[serde(deny_unknown_fields)]` fails the assertion.
+///
+/// ```should_panic
+/// # use serde_derive::Deserialize;
+/// # use serde_test::{assert_de_tokens_ignore, Token};
+/// #
+/// #[derive(Deserialize, PartialEq, Debug)]
+/// #[serde(deny_unknown_fields)]
+/// struct Strict {
+///     a: u8,
+/// }
+///
+/// assert_de_tokens_ignore(
+///     &Strict { a: 1 },
+///     &[
+///         Token::Struct { name: "Strict", len: 1 },
+///         Token::Str("a"),
+///         Token::U8(1),
+///         Token::StructEnd,
+///     ],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_ignore<T>(value: &T, tokens: &[Token])
+where
+    T: DeserializeOwned + PartialEq + Debug,
+{
+    const UNKNOWN: &[Token] = &[
+        Token::Str("__serde_test_unknown"),
+        Token::Seq { len: Some(3) },
+        Token::U8(0),
+        Token::Map { len: Some(1) },
+        Token::Str("key"),
+        Token::Bytes(b"value"),
+        Token::MapEnd,
+        Token::Unit,
+        Token::SeqEnd,
+    ];
+
+    assert_de_tokens(value, tokens);
+
+    let mut injections = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        let header = match *token {
+            Token::Struct { name, len } => Token::Struct { name, len: len + 1 },
+            Token::StructVariant { name, variant, len } => Token::StructVariant {
+                name,
+                variant,
+                len: len + 1,
+            },
+            _ => continue,
+        };
+        let injected = [&tokens[..i], &[header], UNKNOWN, &tokens[i + 1..]].concat();
+        assert_de_tokens(value, &injected);
+        injections += 1;
+    }
+
+    if injections == 0 {
+        let mismatch =
+            Mismatch::new("no Struct or StructVariant token to add an unknown field to".to_owned());
+        fail(&Config::new(), tokens, mismatch);
+    }
+}
+
+/// Asserts that the given `tokens` deserialize into `value` without the
+/// deserialized value borrowing any data from the input.
+///
+/// Tokens that would normally lend data to the `Deserialize` impl, such as
+/// `Token::BorrowedStr`, are delivered as transient data instead. This
+/// catches types meant to be `DeserializeOwned` that have picked up a borrowed
+/// field.
+///
+/// ```
+/// # use serde_derive::{Deserialize, Serialize};
+/// # use serde_test::{assert_de_owned, Token};
+/// #
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct S {
+///     name: String,
+/// }
+///
+/// let s = S {
+///     name: "owned".to_owned(),
+/// };
+/// assert_de_owned(
+///     &s,
+///     &[
+///         Token::Struct { name: "S", len: 1 },
+///         Token::BorrowedStr("name"),
+///         Token::BorrowedStr("owned"),
+///         Token::StructEnd,
+///     ],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_owned<T>(value: &T, tokens: &[Token])
+where
+    T: DeserializeOwned + PartialEq + Debug,
+{
+    assert_de_tokens_using(value, tokens, &Config::new(), || {
+        Deserializer::owned(tokens)
+    });
+}
+
+/// Asserts that the given `tokens` deserialize into `value`, and that every
+/// `Token::BorrowedStr` and `Token::BorrowedBytes` among them was borrowed by
+/// the deserialized value rather than copied.
+///
+/// Borrowing is checked by serializing the deserialized value against the
+/// same tokens: a string or bytes matching a borrowed token must point at
+/// the token's own data. This proves that a field marked `#[serde(borrow)]`
+/// went through `visit_borrowed_str` or `visit_borrowed_bytes`, where an impl
+/// that fell back to `visit_str` would hold a copy.
+///
+/// ```should_panic
+/// # use serde_derive::{Deserialize, Serialize};
+/// # use serde_test::{assert_de_tokens_borrowed, Token};
+/// # use std::borrow::Cow;
+/// #
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct S<'a> {
+///     // Missing #[serde(borrow)], so the Cow is always owned.
+///     name: Cow<'a, str>,
+/// }
+///
+/// assert_de_tokens_borrowed(
+///     &S { name: Cow::Borrowed("x") },
+///     &[
+///         Token::Struct { name: "S", len: 1 },
+///         Token::Str("name"),
+///         Token::BorrowedStr("x"),
+///         Token::StructEnd,
+///     ],
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_borrowed<'de, T>(value: &T, tokens: &'de [Token])
+where
+    T: Serialize + Deserialize<'de> + PartialEq + Debug,
+{
+    let deserialized = match assert_de_result(
+        tokens,
+        "deserialize",
+        |de| T::deserialize(de),
+        |v| check_value(v, value),
+    ) {
+        Some(deserialized) => deserialized,
+        None => return,
+    };
+
+    let config = Config {
+        check_borrowed: true,
+        ..Config::new()
+    };
+    let mut ser = Serializer::with_config(tokens, config.clone());
+    if let Err(err) = deserialized.serialize(&mut ser) {
+        let mismatch = Mismatch::from_error(
+            "deserialized value does not serialize back to the tokens",
+            &err,
+        );
+        fail(&config, tokens, mismatch);
+    }
+}
+
+/// Runs `deserialize` against a deserializer over `tokens` and reports a
+/// failure if the impl errors, if `check` rejects the value, or if tokens are
+/// left over. Returns the value if none of that happened.
+///
+/// `what` names the call being tested in the error message, such as
+/// `deserialize_in_place`.
+#[track_caller]
+fn assert_de_result<'de, T, D, C>(
+    tokens: &'de [Token],
+    what: &str,
+    deserialize: D,
+    check: C,
+) -> Option<T>
+where
+    D: FnOnce(&mut Deserializer<'de>) -> Result<T, Error>,
+    C: FnOnce(&T) -> Result<(), Mismatch>,
+{
+    let config = Config::new();
+    let mut de = Deserializer::new(tokens);
+    let v = match deserialize(&mut de) {
+        Ok(v) => v,
+        Err(e) => {
+            let context = format!("tokens failed to {}", what);
+            fail(&config, tokens, Mismatch::from_error(&context, &e));
+            return None;
+        }
+    };
+    if let Err(mismatch) = check(&v) {
+        fail(&config, tokens, mismatch);
+        return None;
+    }
+    if de.remaining() > 0 {
+        fail(&config, tokens, Mismatch::remaining(tokens, de.index()));
+        return None;
+    }
+    Some(v)
+}
+
+/// Compares a deserialized value against the expected one.
+fn check_value<T>(actual: &T, expected: &T) -> Result<(), Mismatch>
+where
+    T: ?Sized + PartialEq + Debug,
+{
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(Mismatch::values(actual, expected))
+    }
+}
+
+#[track_caller]
+fn assert_de_tokens_using<'de, T, F>(
+    value: &T,
+    tokens: &[Token],
+    config: &Config,
+    new_deserializer: F,
+) -> Report
+where
+    T: Deserialize<'de> + PartialEq + Debug,
+    F: Fn() -> Deserializer<'de>,
+{
+    let mut de = new_deserializer();
+    let mut deserialized_val = match T::deserialize(&mut de) {
+        Ok(v) => {
+            if v != *value {
+                fail(config, tokens, Mismatch::values(&v, value));
+                return de.report();
+            }
+            v
+        }
+        Err(e) => {
+            let mismatch = Mismatch::from_error("tokens failed to deserialize", &e);
+            fail(config, tokens, mismatch);
+            return de.report();
+        }
+    };
+    let report = de.report();
+    if de.remaining() > 0 && !config.allow_trailing {
+        fail(config, tokens, Mismatch::remaining(tokens, de.index()));
+        return report;
+    }
+
+    // Do the same thing for deserialize_in_place. This isn't *great* because a
+    // no-op impl of deserialize_in_place can technically succeed here. Still,
+    // this should catch a lot of junk.
+    let mut de = new_deserializer();
+    match T::deserialize_in_place(&mut de, &mut deserialized_val) {
+        Ok(()) => {
+            if deserialized_val != *value {
+                let mismatch = Mismatch::values(&deserialized_val, value);
+                fail(config, tokens, mismatch);
+                return report;
+            }
+        }
+        Err(e) => {
+            let mismatch = Mismatch::from_error("tokens failed to deserialize_in_place", &e);
+            fail(config, tokens, mismatch);
+            return report;
+        }
+    }
+    if de.remaining() > 0 && !config.allow_trailing {
+        fail(config, tokens, Mismatch::remaining(tokens, de.index()));
+    }
+    report
+}
+
+/// Asserts that the given `tokens` deserialize into `value` while keeping at
+/// most `max_bytes` of memory allocated at any one time.
+///
+/// The deserialized value itself counts towards the limit. Requires
+/// [`TrackingAllocator`] to be installed as the global allocator.
+///
+/// [`TrackingAllocator`]: crate::TrackingAllocator
+///
+/// ```
+/// # use serde_test::{assert_de_tokens_peak_memory, Token, TrackingAllocator};
+/// #
+/// #[global_allocator]
+/// static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+///
+/// # fn main() {
+/// let s = String::from("12345678");
+/// assert_de_tokens_peak_memory(&s, &[Token::Str("12345678")], 8);
+/// # }
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_peak_memory<'de, T>(value: &T, tokens: &'de [Token], max_bytes: usize)
+where
+    T: Deserialize<'de> + PartialEq + Debug,
+{
+    let config = Config::new();
+    let mut peak = 0;
+    let deserialized = assert_de_result(
+        tokens,
+        "deserialize",
+        |de| {
+            let (result, bytes) = peak_memory(|| T::deserialize(de));
+            peak = bytes;
+            result
+        },
+        |v: &T| check_value(v, value),
+    );
+    if deserialized.is_some() && peak > max_bytes {
+        let mismatch = Mismatch::new(format!(
+            "deserialization had {} bytes live at peak, more than the limit of {}",
+            peak, max_bytes,
+        ));
+        fail(&config, tokens, mismatch);
+    }
+}
+
+/// Asserts that the given `tokens` yield `error` when deserializing.
+///
+/// ```
+/// # use serde_derive::{Deserialize, Serialize};
+/// # use serde_test::{assert_de_tokens_error, Token};
+/// #
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// #[serde(deny_unknown_fields)]
+/// struct S {
+///     a: u8,
+///     b: u8,
+/// }
+///
+/// assert_de_tokens_error::<S>(
+///     &[
+///         Token::Struct { name: "S", len: 2 },
+///         Token::Str("x"),
+///     ],
+///     "unknown field `x`, expected `a` or `b`",
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_error<'de, T>(tokens: &'de [Token], error: &str)
+where
+    T: Deserialize<'de>,
+{
+    let config = Config::new();
+    let mut de = Deserializer::new(tokens);
+    match T::deserialize(&mut de) {
+        Ok(_) => {
+            let mismatch = Mismatch::new("tokens deserialized successfully".to_owned());
+            return fail(&config, tokens, mismatch);
+        }
+        Err(e) => {
+            if e != *error {
+                return fail(&config, tokens, Mismatch::values(&e.to_string(), error));
+            }
+        }
+    }
+
+    // There may be one token left if a peek caused the error
+    de.next_token_opt();
+
+    if de.remaining() > 0 {
+        fail(&config, tokens, Mismatch::remaining(tokens, de.index()));
+    }
+}
+
+/// Like [`assert_tokens`], but returns the first failure instead of
+/// panicking.
+///
+/// This suits property-testing closures and harnesses that aggregate
+/// failures, where a panic would abort the run.
+///
+/// # Errors
+///
+/// Returns the [`Mismatch`] that `assert_tokens` would have panicked with.
+///
+/// ```
+/// # use serde_test::{try_assert_tokens, Token};
+/// #
+/// assert!(try_assert_tokens(&1u8, &[Token::U8(1)]).is_ok());
+///
+/// let mismatch = try_assert_tokens(&1u8, &[Token::U16(1)]).unwrap_err();
+/// assert_eq!(mismatch.index(), Some(0));
+/// assert_eq!(mismatch.expected(), Some(Token::U16(1)));
+/// assert_eq!(mismatch.actual(), Some("U8(1)"));
+/// ```
+pub fn try_assert_tokens<'de, T>(value: &T, tokens: &'de [Token]) -> Result<(), Mismatch>
+where
+    T: Serialize + Deserialize<'de> + PartialEq + Debug,
+{
+    catch_failure(|| assert_tokens(value, tokens))
+}
+
+/// Like [`assert_ser_tokens`], but returns the first failure instead of
+/// panicking.
+///
+/// # Errors
+///
+/// Returns the [`Mismatch`] that `assert_ser_tokens` would have panicked
+/// with.
+pub fn try_assert_ser_tokens<T>(value: &T, tokens: &[Token]) -> Result<(), Mismatch>
+where
+    T: ?Sized + Serialize,
+{
+    catch_failure(|| assert_ser_tokens(value, tokens))
+}
+
+/// Like [`assert_de_tokens`], but returns the first failure instead of
+/// panicking.
+///
+/// # Errors
+///
+/// Returns the [`Mismatch`] that `assert_de_tokens` would have panicked
+/// with.
+pub fn try_assert_de_tokens<'de, T>(value: &T, tokens: &'de [Token]) -> Result<(), Mismatch>
+where
+    T: Deserialize<'de> + PartialEq + Debug,
+{
+    catch_failure(|| assert_de_tokens(value, tokens))
+}
+
+/// Deserializes a `T` from the given `tokens` and returns whatever the impl
+/// produced.
+///
+/// Nothing is asserted, so a test can inspect the error any way it likes,
+/// such as its [`kind`] or a fragment of its message, where
+/// [`assert_de_tokens_error`] can only compare the full message. Tokens left
+/// over after the value are ignored.
+///
+/// # Errors
+///
+/// Returns the error that the `Deserialize` impl or the token deserializer
+/// produced.
+///
+/// [`kind`]: Error::kind
+///
+/// ```
+/// # use serde_derive::Deserialize;
+/// # use serde_test::{try_de_tokens, ErrorKind, Token};
+/// #
+/// #[derive(Deserialize, Debug)]
+/// #[serde(deny_unknown_fields)]
+/// struct S {
+///     a: u8,
+/// }
+///
+/// let err = try_de_tokens::<S>(&[
+///     Token::Struct { name: "S", len: 1 },
+///     Token::Str("x"),
+/// ])
+/// .unwrap_err();
+/// assert_eq!(err.kind(), ErrorKind::UnknownField);
+/// assert!(err.to_string().starts_with("unknown field `x`"));
+/// ```
+pub fn try_de_tokens<'de, T>(tokens: &'de [Token]) -> Result<T, Error>
+where
+    T: Deserialize<'de>,
+{
+    T::deserialize(&mut Deserializer::new(tokens))
+}
+
+/// Like [`try_de_tokens`], but the deserializer reports itself as
+/// human-readable.
+///
+/// # Errors
+///
+/// Returns the error that deserialization produced, as for `try_de_tokens`.
+pub fn try_de_tokens_readable<'de, T>(tokens: &'de [Token]) -> Result<T, Error>
+where
+    T: Deserialize<'de>,
+{
+    T::deserialize(Readable(&mut Deserializer::new(tokens)))
+}
+
+/// Like [`try_de_tokens`], but the deserializer reports itself as compact.
+///
+/// # Errors
+///
+/// Returns the error that deserialization produced, as for `try_de_tokens`.
+///
+/// ```
+/// # use serde_test::{try_de_tokens_compact, try_de_tokens_readable, Token};
+/// # use std::net::Ipv4Addr;
+/// #
+/// let tokens = [Token::Str("127.0.0.1")];
+/// assert!(try_de_tokens_readable::<Ipv4Addr>(&tokens).is_ok());
+///
+/// let err = try_de_tokens_compact::<Ipv4Addr>(&tokens).unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "invalid type: string \"127.0.0.1\", expected an array of length 4",
+/// );
+/// ```
+pub fn try_de_tokens_compact<'de, T>(tokens: &'de [Token]) -> Result<T, Error>
+where
+    T: Deserialize<'de>,
+{
+    T::deserialize(Compact(&mut Deserializer::new(tokens)))
+}
+
+/// Asserts that the given `tokens` yield an error of the given `kind` when
+/// deserializing, whatever its message.
+///
+/// ```
+/// # use serde_derive::Deserialize;
+/// # use serde_test::{assert_de_error_kind, ErrorKind, Token};
+/// #
+/// #[derive(Deserialize, Debug)]
+/// enum E {
+///     A,
+/// }
+///
+/// assert_de_error_kind::<E>(
+///     &[Token::UnitVariant { name: "E", variant: "B" }],
+///     ErrorKind::UnknownVariant,
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_error_kind<'de, T>(tokens: &'de [Token], kind: ErrorKind)
+where
+    T: Deserialize<'de>,
+{
+    assert_de_error_matches::<T, _>(tokens, &format!("{:?}", kind), |e| e.kind() == kind);
+}
+
+/// Asserts that the given `tokens` yield an invalid type or invalid value
+/// error of the given `kind` about the `unexpected` value when deserializing,
+/// whatever its message.
+///
+/// ```
+/// # use serde::de::Unexpected;
+/// # use serde_test::{assert_de_error_unexpected, ErrorKind, Token};
+/// #
+/// assert_de_error_unexpected::<u8>(
+///     &[Token::I32(-1)],
+///     ErrorKind::InvalidValue,
+///     Unexpected::Signed(-1),
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_error_unexpected<'de, T>(
+    tokens: &'de [Token],
+    kind: ErrorKind,
+    unexpected: Unexpected,
+) where
+    T: Deserialize<'de>,
+{
+    let unexpected = unexpected.to_string();
+    let describe = format!("{:?} about {}", kind, unexpected);
+    assert_de_error_matches::<T, _>(tokens, &describe, |e| {
+        e.kind() == kind && e.unexpected() == Some(unexpected.as_str())
+    });
+}
+
+/// Like [`assert_de_tokens_error`], but only requires the error message to
+/// contain `fragment`, so that the test survives rewording elsewhere in the
+/// message.
+///
+/// ```
+/// # use serde_test::{assert_de_tokens_error_contains, Token};
+/// #
+/// assert_de_tokens_error_contains::<u8>(&[Token::Str("1")], "expected u8");
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_error_contains<'de, T>(tokens: &'de [Token], fragment: &str)
+where
+    T: Deserialize<'de>,
+{
+    let describe = format!("an error containing {:?}", fragment);
+    assert_de_error_matches::<T, _>(tokens, &describe, |e| e.to_string().contains(fragment));
+}
+
+#[track_caller]
+fn assert_de_error_matches<'de, T, F>(tokens: &'de [Token], describe: &str, matches: F)
+where
+    T: Deserialize<'de>,
+    F: FnOnce(&Error) -> bool,
+{
+    let config = Config::new();
+    let mut de = Deserializer::new(tokens);
+    match T::deserialize(&mut de) {
+        Ok(_) => {
+            let mismatch = Mismatch::new("tokens deserialized successfully".to_owned());
+            return fail(&config, tokens, mismatch);
+        }
+        Err(e) => {
+            if !matches(&e) {
+                let actual = match e.unexpected() {
+                    Some(unexpected) => format!("{:?} about {}: {}", e.kind(), unexpected, e),
+                    None => format!("{:?}: {}", e.kind(), e),
+                };
+                return fail(&config, tokens, Mismatch::values(&actual, describe));
+            }
+        }
+    }
+
+    // There may be one token left if a peek caused the error
+    de.next_token_opt();
+
+    if de.remaining() > 0 {
+        fail(&config, tokens, Mismatch::remaining(tokens, de.index()));
+    }
+}
+
+/// Asserts that the given `tokens` yield `error` when deserializing, after
+/// exactly `consumed` of them were taken by the deserializer.
+///
+/// Unlike [`assert_de_tokens_error`], the tokens may continue past the point
+/// of failure. This distinguishes an impl that fails as soon as it sees a bad
+/// tag from one that fails only after consuming the whole body, which matters
+/// for formats that resynchronize after an error. A token that was only
+/// peeked at does not count as consumed.
+///
+/// If the error came at a different position, the failure points at the
+/// token following the last one consumed, and [`Mismatch::index`] reports
+/// how many were consumed.
+///
+/// ```
+/// # use serde_derive::Deserialize;
+/// # use serde_test::{assert_de_tokens_error_at, Token};
+/// #
+/// #[derive(Deserialize, Debug)]
+/// #[serde(deny_unknown_fields)]
+/// struct S {
+///     a: u8,
+/// }
+///
+/// assert_de_tokens_error_at::<S>(
+///     &[
+///         Token::Struct { name: "S", len: 1 },
+///         Token::Str("x"),
+///         Token::U8(0),
+///         Token::StructEnd,
+///     ],
+///     "unknown field `x`, expected `a`",
+///     2,
+/// );
+/// ```
+#[track_caller]
+pub fn assert_de_tokens_error_at<'de, T>(tokens: &'de [Token], error: &str, consumed: usize)
+where
+    T: Deserialize<'de>,
+{
+    let config = Config::new();
+    let mut de = Deserializer::new(tokens);
+    match T::deserialize(&mut de) {
+        Ok(_) => {
+            let mismatch = Mismatch::new("tokens deserialized successfully".to_owned());
+            return fail(&config, tokens, mismatch);
+        }
+        Err(e) => {
+            if e != *error {
+                return fail(&config, tokens, Mismatch::values(&e.to_string(), error));
+            }
+        }
+    }
+
+    if de.index() != consumed {
+        let mismatch = Mismatch::error_position(tokens, consumed, de.index());
+        fail(&config, tokens, mismatch);
+    }
+}
+
+/// Asserts that the `Visitor` used to deserialize `T` describes what it
+/// expects using the given message.
+///
+/// The message is the one produced by `Visitor::expecting` and appears in
+/// errors such as "invalid type: integer `0`, expected struct S".
+///
+/// ```
+/// # use serde_derive::Deserialize;
+/// # use serde_test::assert_expecting;
+/// #
+/// #[derive(Deserialize)]
+/// struct S {
+///     a: u8,
+/// }
+///
+/// assert_expecting::<S>("struct S");
+/// assert_expecting::<Option<u8>>("option");
+/// ```
+#[track_caller]
+pub fn assert_expecting<'de, T>(expecting: &str)
+where
+    T: Deserialize<'de>,
+{
+    let config = Config::new();
+    match T::deserialize(ExpectingDeserializer) {
+        Ok(_) => {
+            let mismatch =
+                Mismatch::new("value deserialized without asking for a visitor".to_owned());
+            fail(&config, &[], mismatch);
+        }
+        Err(e) => {
+            if e != *expecting {
+                fail(&config, &[], Mismatch::values(&e.to_string(), expecting));
+            }
+        }
     }
 }
@@ -0,0 +1,501 @@
+use crate::error::Error;
+use crate::intern::{intern, intern_bytes};
+use crate::path::Path;
+use crate::token::Token;
+use serde::ser::{self, Serialize};
+
+/// A `Serializer` that records the tokens a value serializes to.
+struct TokenSerializer {
+    tokens: Vec<Token>,
+    record: bool,
+    /// The path of each recorded token, if provenance is being captured.
+    paths: Option<Vec<String>>,
+    /// The rendered segments of the path of the current value.
+    segments: Vec<String>,
+    /// The index of the next element of each open sequence or tuple.
+    indices: Vec<usize>,
+    /// The rendered segment of the map key just serialized.
+    key: String,
+}
+
+impl TokenSerializer {
+    fn new(record: bool, paths: bool) -> Self {
+        TokenSerializer {
+            tokens: Vec::new(),
+            record,
+            paths: if paths { Some(Vec::new()) } else { None },
+            segments: Vec::new(),
+            indices: Vec::new(),
+            key: String::new(),
+        }
+    }
+
+    // Returns a `Result` so that it can end the `Serializer` methods.
+    #[allow(clippy::unnecessary_wraps)]
+    fn push(&mut self, token: Token) -> Result<(), Error> {
+        if self.record {
+            self.tokens.push(token);
+            if let Some(paths) = &mut self.paths {
+                let path = self.segments.concat();
+                let path = match path.strip_prefix('.') {
+                    Some(path) => path.to_owned(),
+                    None => path,
+                };
+                paths.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Serializes `value` one path segment further down, if paths are
+    /// captured.
+    fn serialize_at<T>(&mut self, segment: String, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.segments.push(segment);
+        let result = value.serialize(&mut *self);
+        self.segments.pop();
+        result
+    }
+
+    fn open_elements(&mut self) {
+        self.indices.push(0);
+    }
+
+    fn close_elements(&mut self) {
+        self.indices.pop();
+    }
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if self.paths.is_none() {
+            return value.serialize(self);
+        }
+        let index = match self.indices.last_mut() {
+            Some(next) => {
+                *next += 1;
+                *next - 1
+            }
+            None => 0,
+        };
+        self.serialize_at(format!("[{}]", index), value)
+    }
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if self.paths.is_none() {
+            self.push(Token::Str(key))?;
+            return value.serialize(self);
+        }
+        self.segments.push(format!(".{}", key));
+        self.push(Token::Str(key))?;
+        let result = value.serialize(&mut *self);
+        self.segments.pop();
+        result
+    }
+
+    /// Enters a variant for the rest of an enum value, if paths are captured.
+    fn enter_variant(&mut self, variant: &'static str) {
+        if self.paths.is_some() {
+            self.segments.push(format!(".{}", variant));
+        }
+    }
+
+    fn leave_variant(&mut self) {
+        if self.paths.is_some() {
+            self.segments.pop();
+        }
+    }
+}
+
+/// Returns the tokens that `value` serializes to.
+///
+/// Strings and bytes in the value are turned into `'static` token payloads
+/// with [`intern`] and [`intern_bytes`], which leak each distinct one for the
+/// rest of the process. This is mostly useful for checking
+/// properties of a stream that would be tedious to write out by hand, or for
+/// printing the stream of a new type to paste into a test.
+///
+/// [`intern_bytes`]: crate::intern_bytes
+///
+/// ```
+/// # use serde_test::{to_tokens, Token};
+/// #
+/// let tokens = to_tokens(&(1u8, "a".to_owned()));
+/// assert_eq!(
+///     tokens,
+///     [
+///         Token::Tuple { len: 2 },
+///         Token::U8(1),
+///         Token::Str("a"),
+///         Token::TupleEnd,
+///     ],
+/// );
+/// ```
+pub fn to_tokens<T>(value: &T) -> Vec<Token>
+where
+    T: ?Sized + Serialize,
+{
+    let mut ser = TokenSerializer::new(true, false);
+    match value.serialize(&mut ser) {
+        Ok(()) => ser.tokens,
+        Err(err) => panic!("value failed to serialize: {}", err),
+    }
+}
+
+/// Returns the tokens that `value` serializes to, each paired with the path
+/// of the value that produced it, such as `servers[3].port`.
+///
+/// The path names struct fields, enum variants, sequence indices and map
+/// keys, whatever wrapper types sit in between. This tells which part of a
+/// deeply generic value a token came from when a stream does not look as
+/// expected.
+///
+/// ```
+/// # use serde_derive::Serialize;
+/// # use serde_test::{to_tokens_with_paths, Token};
+/// #
+/// #[derive(Serialize)]
+/// enum Shape {
+///     Polygon { points: Vec<(i32, i32)> },
+/// }
+///
+/// let shape = Shape::Polygon {
+///     points: vec![(0, 0), (4, 3)],
+/// };
+/// let tokens = to_tokens_with_paths(&shape);
+/// assert_eq!(tokens[8], (Token::I32(4), "Polygon.points[1][0]".to_owned()));
+///
+/// for (token, path) in &tokens {
+///     println!("{:<30} {}", path, token);
+/// }
+/// ```
+pub fn to_tokens_with_paths<T>(value: &T) -> Vec<(Token, String)>
+where
+    T: ?Sized + Serialize,
+{
+    let mut ser = TokenSerializer::new(true, true);
+    match value.serialize(&mut ser) {
+        Ok(()) => ser.tokens.into_iter().zip(ser.paths.unwrap()).collect(),
+        Err(err) => panic!("value failed to serialize: {}", err),
+    }
+}
+
+/// Serializes `value` without recording or checking any of its tokens, for a
+/// value matched by `Token::SkipValue`.
+pub(crate) fn skip<T>(value: &T) -> Result<(), Error>
+where
+    T: ?Sized + Serialize,
+{
+    let mut ser = TokenSerializer::new(false, false);
+    value.serialize(&mut ser)
+}
+
+macro_rules! capture_scalar {
+    ($($method:ident($ty:ty) => $token:ident,)*) => {
+        $(
+            fn $method(self, v: $ty) -> Result<(), Error> {
+                self.push(Token::$token(v))
+            }
+        )*
+    };
+}
+
+impl<'a> ser::Serializer for &'a mut TokenSerializer {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    capture_scalar! {
+        serialize_bool(bool) => Bool,
+        serialize_i8(i8) => I8,
+        serialize_i16(i16) => I16,
+        serialize_i32(i32) => I32,
+        serialize_i64(i64) => I64,
+        serialize_u8(u8) => U8,
+        serialize_u16(u16) => U16,
+        serialize_u32(u32) => U32,
+        serialize_u64(u64) => U64,
+        serialize_f32(f32) => F32,
+        serialize_f64(f64) => F64,
+        serialize_char(char) => Char,
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        if self.record {
+            self.push(Token::Str(intern(v)))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        if self.record {
+            self.push(Token::Bytes(intern_bytes(v)))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.push(Token::Unit)
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<(), Error> {
+        self.push(Token::UnitStruct { name })
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.push(Token::UnitVariant { name, variant })
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(Token::NewtypeStruct { name })?;
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(Token::NewtypeVariant { name, variant })?;
+        self.enter_variant(variant);
+        let result = value.serialize(&mut *self);
+        self.leave_variant();
+        result
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        self.push(Token::None)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(Token::Some)?;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self, Error> {
+        self.push(Token::Seq { len })?;
+        self.open_elements();
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self, Error> {
+        self.push(Token::Tuple { len })?;
+        self.open_elements();
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, name: &'static str, len: usize) -> Result<Self, Error> {
+        self.push(Token::TupleStruct { name, len })?;
+        self.open_elements();
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self, Error> {
+        self.push(Token::TupleVariant { name, variant, len })?;
+        self.enter_variant(variant);
+        self.open_elements();
+        Ok(self)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self, Error> {
+        self.push(Token::Map { len })?;
+        Ok(self)
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self, Error> {
+        self.push(Token::Struct { name, len })?;
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self, Error> {
+        self.push(Token::StructVariant { name, variant, len })?;
+        self.enter_variant(variant);
+        Ok(self)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        panic!(
+            "Types which have different human-readable and compact representations \
+             must explicitly mark their test cases with `serde_test::Configure`"
+        );
+    }
+}
+
+impl<'a> ser::SerializeSeq for &'a mut TokenSerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        TokenSerializer::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.close_elements();
+        self.push(Token::SeqEnd)
+    }
+}
+
+impl<'a> ser::SerializeTuple for &'a mut TokenSerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        TokenSerializer::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.close_elements();
+        self.push(Token::TupleEnd)
+    }
+}
+
+impl<'a> ser::SerializeTupleStruct for &'a mut TokenSerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        TokenSerializer::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.close_elements();
+        self.push(Token::TupleStructEnd)
+    }
+}
+
+impl<'a> ser::SerializeTupleVariant for &'a mut TokenSerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        TokenSerializer::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.close_elements();
+        self.leave_variant();
+        self.push(Token::TupleVariantEnd)
+    }
+}
+
+impl<'a> ser::SerializeMap for &'a mut TokenSerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let start = self.tokens.len();
+        key.serialize(&mut **self)?;
+        if self.paths.is_some() {
+            let mut path = Path::default();
+            path.push_key(&self.tokens[start..]);
+            self.key = path.to_string();
+        }
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if self.paths.is_none() {
+            return value.serialize(&mut **self);
+        }
+        let key = std::mem::take(&mut self.key);
+        self.serialize_at(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.push(Token::MapEnd)
+    }
+}
+
+impl<'a> ser::SerializeStruct for &'a mut TokenSerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        TokenSerializer::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.push(Token::StructEnd)
+    }
+}
+
+impl<'a> ser::SerializeStructVariant for &'a mut TokenSerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        TokenSerializer::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.leave_variant();
+        self.push(Token::StructVariantEnd)
+    }
+}
@@ -0,0 +1,1089 @@
+use crate::mismatch::Mismatch;
+use crate::output::fail;
+use crate::token::{token_matches, Token};
+use crate::tokens::Section;
+
+/// Options that change how the token serializer and deserializer behave
+/// during an assertion.
+///
+/// Every option is disabled by default, which gives the same behavior as the
+/// plain assertion functions. Options are enabled with builder methods and
+/// the configuration is then passed to one of the `*_with_config` assertion
+/// functions, such as [`assert_tokens_with_config`], which checks both
+/// directions.
+///
+/// [`assert_tokens_with_config`]: crate::assert_tokens_with_config
+///
+/// ```
+/// # use serde_test::{assert_de_tokens_with_config, assert_tokens_with_config, Config, Token};
+/// # use std::net::Ipv4Addr;
+/// #
+/// let config = Config::new().large_integers_as_strings(true);
+/// assert_de_tokens_with_config(
+///     &"9007199254740993".to_owned(),
+///     &[Token::U64(9007199254740993)],
+///     config,
+/// );
+///
+/// let config = Config::new().human_readable(true).exact_numbers(true);
+/// assert_tokens_with_config(
+///     &(Ipv4Addr::LOCALHOST, 1u8),
+///     &[
+///         Token::Tuple { len: 2 },
+///         Token::Str("127.0.0.1"),
+///         Token::U8(1),
+///         Token::TupleEnd,
+///     ],
+///     config,
+/// );
+/// ```
+#[derive(Clone, Debug, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Config {
+    pub(crate) variant_identifiers: Identifier,
+    pub(crate) field_identifiers: Identifier,
+    pub(crate) enums_as_maps: bool,
+    pub(crate) ignore_struct_names: bool,
+    pub(crate) self_describing: bool,
+    pub(crate) duplicate_keys: DuplicateKeys,
+    pub(crate) map_keys: MapKeys,
+    pub(crate) map_size_hint: SizeHint,
+    pub(crate) seq_size_hint: SizeHint,
+    pub(crate) max_calls: Option<usize>,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) detect_cycles: Option<usize>,
+    pub(crate) mismatch_payload: bool,
+    #[cfg(feature = "bytes-as-base64")]
+    pub(crate) bytes_as_base64: bool,
+    pub(crate) owned: bool,
+    pub(crate) borrow_bytes: bool,
+    pub(crate) large_integers_as_strings: bool,
+    pub(crate) exact_numbers: bool,
+    pub(crate) int128_as_strings: bool,
+    pub(crate) reject_non_finite: bool,
+    pub(crate) float_ulps: u64,
+    pub(crate) known_names: Option<&'static [&'static str]>,
+    pub(crate) sections: Vec<Section>,
+    /// What `is_human_readable` answers, instead of panicking, when set.
+    pub(crate) human_readable: Option<bool>,
+    pub(crate) allow_trailing: bool,
+    /// Whether a string or bytes serialized against a `BorrowedStr` or
+    /// `BorrowedBytes` token must be the very data of the token.
+    pub(crate) check_borrowed: bool,
+    pub(crate) check_len_hints: bool,
+    /// The predicates that `Token::Verify` names, most recent last.
+    pub(crate) predicates: Vec<(&'static str, Predicate)>,
+}
+
+/// A check on the token a value serializes to, as used by
+/// [`Config::verify`] and [`MapKeys::Only`].
+pub type Predicate = fn(&Token) -> bool;
+
+impl Config {
+    /// Creates a configuration with every option disabled.
+    #[must_use]
+    pub fn new() -> Self {
+        Config::default()
+    }
+
+    /// Delivers `I64` and `U64` tokens whose magnitude is beyond 2<sup>53</sup>
+    /// &minus; 1 as strings, the way formats bound for JavaScript represent
+    /// integers that do not fit in a double.
+    ///
+    /// Smaller integers are delivered as usual.
+    #[must_use]
+    pub fn large_integers_as_strings(mut self, enable: bool) -> Self {
+        self.large_integers_as_strings = enable;
+        self
+    }
+
+    /// Fails deserialization if the `Deserialize` impl asks for a different
+    /// kind of number than the next token holds, for example
+    /// `deserialize_u64` for a `U8` token.
+    ///
+    /// Fixed-width binary formats rely on these hints to know how many bytes
+    /// to read, so an impl that sends the wrong one would misread its input.
+    ///
+    /// ```should_panic
+    /// # use serde::de::{Deserialize, Deserializer};
+    /// # use serde_test::{assert_de_tokens_with_config, Config, Token};
+    /// #
+    /// #[derive(PartialEq, Debug)]
+    /// struct Byte(u8);
+    ///
+    /// impl<'de> Deserialize<'de> for Byte {
+    ///     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    ///     where
+    ///         D: Deserializer<'de>,
+    ///     {
+    ///         // Asks for a u64 although the value is serialized as a u8.
+    ///         let v = u64::deserialize(deserializer)?;
+    ///         Ok(Byte(v as u8))
+    ///     }
+    /// }
+    ///
+    /// let config = Config::new().exact_numbers(true);
+    /// assert_de_tokens_with_config(&Byte(1), &[Token::U8(1)], config);
+    /// ```
+    #[must_use]
+    pub fn exact_numbers(mut self, enable: bool) -> Self {
+        self.exact_numbers = enable;
+        self
+    }
+
+    /// Fails serialization of a NaN or infinite float, the way JSON and TOML
+    /// serializers do.
+    ///
+    /// Types whose fields can be non-finite can then prove that they sanitize
+    /// such values before serializing to those formats.
+    ///
+    /// ```should_panic
+    /// # use serde_test::{assert_ser_tokens_with_config, Config, Token};
+    /// #
+    /// let config = Config::new().reject_non_finite(true);
+    /// assert_ser_tokens_with_config(&f64::INFINITY, &[Token::F64(f64::INFINITY)], config);
+    /// ```
+    #[must_use]
+    pub fn reject_non_finite(mut self, enable: bool) -> Self {
+        self.reject_non_finite = enable;
+        self
+    }
+
+    /// Accepts a serialized `f32` or `f64` that is within `ulps` units in the
+    /// last place of the expected `F32` or `F64` token.
+    ///
+    /// Serialize impls that compute their output, such as by converting units
+    /// or rounding, can then be checked against the intended value instead of
+    /// the exact bits their arithmetic happens to produce. NaN never matches.
+    ///
+    /// ```
+    /// # use serde_test::{assert_ser_tokens_with_config, Config, Token};
+    /// #
+    /// let config = Config::new().float_ulps(4);
+    /// assert_ser_tokens_with_config(&(0.1 + 0.2), &[Token::F64(0.3)], config);
+    /// ```
+    #[must_use]
+    pub fn float_ulps(mut self, ulps: u64) -> Self {
+        self.float_ulps = ulps;
+        self
+    }
+
+    /// Fails serialization if the `Serialize` impl passes a type, variant or
+    /// field name that is not in `names`.
+    ///
+    /// Names are compared by value. This catches a misspelled name in a
+    /// hand-written or macro-generated impl, which most formats never notice
+    /// because they do not write struct and variant names out.
+    ///
+    /// ```should_panic
+    /// # use serde::ser::{Serialize, SerializeStruct, Serializer};
+    /// # use serde_test::{assert_ser_tokens_with_config, Config, Token};
+    /// #
+    /// struct Point {
+    ///     x: i32,
+    /// }
+    ///
+    /// impl Serialize for Point {
+    ///     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    ///     where
+    ///         S: Serializer,
+    ///     {
+    ///         let mut s = serializer.serialize_struct("Piont", 1)?;
+    ///         s.serialize_field("x", &self.x)?;
+    ///         s.end()
+    ///     }
+    /// }
+    ///
+    /// let config = Config::new().known_names(&["Point", "x"]);
+    /// assert_ser_tokens_with_config(
+    ///     &Point { x: 1 },
+    ///     &[
+    ///         Token::Struct { name: "*", len: 1 },
+    ///         Token::Str("x"),
+    ///         Token::I32(1),
+    ///         Token::StructEnd,
+    ///     ],
+    ///     config,
+    /// );
+    /// ```
+    #[must_use]
+    pub fn known_names(mut self, names: &'static [&'static str]) -> Self {
+        self.known_names = Some(names);
+        self
+    }
+
+    /// Represents `i128` and `u128` as decimal strings, the way formats
+    /// without native 128-bit integers encode them.
+    ///
+    /// When serializing, a 128-bit integer is expected to match a `Str` token
+    /// holding its decimal form. When deserializing, a request for a 128-bit
+    /// integer is answered by parsing the next string token.
+    ///
+    /// ```
+    /// # use serde_test::{assert_de_tokens_with_config, assert_ser_tokens_with_config, Config, Token};
+    /// #
+    /// let config = Config::new().int128_as_strings(true);
+    /// let big = u128::MAX;
+    /// let tokens = [Token::Str("340282366920938463463374607431768211455")];
+    /// assert_ser_tokens_with_config(&big, &tokens, config.clone());
+    /// assert_de_tokens_with_config(&big, &tokens, config);
+    /// ```
+    #[must_use]
+    pub fn int128_as_strings(mut self, enable: bool) -> Self {
+        self.int128_as_strings = enable;
+        self
+    }
+
+    /// Selects how the deserializer delivers the identifier of an enum
+    /// variant to the `Deserialize` impl.
+    ///
+    /// The default delivers the variant name the way the tokens spell it.
+    /// `Identifier::Bytes` delivers the name through `visit_bytes` instead.
+    /// `Identifier::Index` delivers the position of the variant in the list
+    /// of variants passed to `deserialize_enum` through `visit_u64`, as
+    /// formats like bincode do.
+    ///
+    /// ```
+    /// # use serde_derive::Deserialize;
+    /// # use serde_test::{assert_de_tokens_with_config, Config, Identifier, Token};
+    /// #
+    /// #[derive(Deserialize, PartialEq, Debug)]
+    /// enum E {
+    ///     A,
+    ///     B,
+    /// }
+    ///
+    /// let config = Config::new().variant_identifiers(Identifier::Index);
+    /// assert_de_tokens_with_config(
+    ///     &E::B,
+    ///     &[Token::UnitVariant {
+    ///         name: "E",
+    ///         variant: "B",
+    ///     }],
+    ///     config,
+    /// );
+    /// ```
+    #[must_use]
+    pub fn variant_identifiers(mut self, identifier: Identifier) -> Self {
+        self.variant_identifiers = identifier;
+        self
+    }
+
+    /// Selects how the deserializer delivers the identifier of a struct field
+    /// to the `Deserialize` impl.
+    ///
+    /// The default delivers the field name the way the tokens spell it.
+    /// `Identifier::Bytes` delivers the name through `visit_bytes` instead.
+    /// `Identifier::Index` delivers the position of the field in the list of
+    /// fields passed to `deserialize_struct` through `visit_u64`, as compact
+    /// formats do. A name missing from that list fails deserialization.
+    ///
+    /// ```
+    /// # use serde_derive::Deserialize;
+    /// # use serde_test::{assert_de_tokens_with_config, Config, Identifier, Token};
+    /// #
+    /// #[derive(Deserialize, PartialEq, Debug)]
+    /// struct S {
+    ///     a: u8,
+    ///     b: u8,
+    /// }
+    ///
+    /// let config = Config::new().field_identifiers(Identifier::Index);
+    /// assert_de_tokens_with_config(
+    ///     &S { a: 1, b: 2 },
+    ///     &[
+    ///         Token::Struct { name: "S", len: 2 },
+    ///         Token::Str("b"),
+    ///         Token::U8(2),
+    ///         Token::Str("a"),
+    ///         Token::U8(1),
+    ///         Token::StructEnd,
+    ///     ],
+    ///     config,
+    /// );
+    /// ```
+    #[must_use]
+    pub fn field_identifiers(mut self, identifier: Identifier) -> Self {
+        self.field_identifiers = identifier;
+        self
+    }
+
+    /// Presents enums to the `Deserialize` impl as a map with a single entry
+    /// from the variant name to its content, the way some self-describing
+    /// formats do, instead of through `EnumAccess`.
+    ///
+    /// A unit variant is presented as just its name.
+    ///
+    /// ```
+    /// # use serde::de::{Deserialize, Deserializer, EnumAccess, MapAccess, VariantAccess, Visitor};
+    /// # use serde_test::{assert_de_tokens, assert_de_tokens_with_config, Config, Token};
+    /// # use std::fmt;
+    /// #
+    /// #[derive(PartialEq, Debug)]
+    /// enum E {
+    ///     N(u8),
+    /// }
+    ///
+    /// impl<'de> Deserialize<'de> for E {
+    ///     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    ///     where
+    ///         D: Deserializer<'de>,
+    ///     {
+    ///         struct EVisitor;
+    ///
+    ///         impl<'de> Visitor<'de> for EVisitor {
+    ///             type Value = E;
+    ///
+    ///             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    ///                 formatter.write_str("enum E")
+    ///             }
+    ///
+    ///             fn visit_enum<A>(self, data: A) -> Result<E, A::Error>
+    ///             where
+    ///                 A: EnumAccess<'de>,
+    ///             {
+    ///                 let (_, variant): (String, _) = data.variant()?;
+    ///                 variant.newtype_variant().map(E::N)
+    ///             }
+    ///
+    ///             fn visit_map<A>(self, mut map: A) -> Result<E, A::Error>
+    ///             where
+    ///                 A: MapAccess<'de>,
+    ///             {
+    ///                 let (_, n): (String, u8) = map.next_entry()?.unwrap();
+    ///                 Ok(E::N(n))
+    ///             }
+    ///         }
+    ///
+    ///         deserializer.deserialize_enum("E", &["N"], EVisitor)
+    ///     }
+    /// }
+    ///
+    /// let tokens = [
+    ///     Token::NewtypeVariant {
+    ///         name: "E",
+    ///         variant: "N",
+    ///     },
+    ///     Token::U8(1),
+    /// ];
+    /// assert_de_tokens(&E::N(1), &tokens);
+    /// assert_de_tokens_with_config(&E::N(1), &tokens, Config::new().enums_as_maps(true));
+    /// ```
+    #[must_use]
+    pub fn enums_as_maps(mut self, enable: bool) -> Self {
+        self.enums_as_maps = enable;
+        self
+    }
+
+    /// Stops comparing the names in `Struct`, `TupleStruct`, `NewtypeStruct`
+    /// and `UnitStruct` tokens, as if every one of them were written as the
+    /// `"*"` wildcard.
+    ///
+    /// This helps when testing through a `#[serde(remote)]` shim or generated
+    /// code whose type name differs from the one in a stream written earlier.
+    /// Enum names are still compared.
+    ///
+    /// ```
+    /// # use serde_derive::Serialize;
+    /// # use serde_test::{assert_ser_tokens_with_config, Config, Token};
+    /// #
+    /// #[derive(Serialize)]
+    /// struct GeneratedPoint(i32, i32);
+    ///
+    /// assert_ser_tokens_with_config(
+    ///     &GeneratedPoint(1, 2),
+    ///     &[
+    ///         Token::TupleStruct { name: "Point", len: 2 },
+    ///         Token::I32(1),
+    ///         Token::I32(2),
+    ///         Token::TupleStructEnd,
+    ///     ],
+    ///     Config::new().ignore_struct_names(true),
+    /// );
+    /// ```
+    #[must_use]
+    pub fn ignore_struct_names(mut self, enable: bool) -> Self {
+        self.ignore_struct_names = enable;
+        self
+    }
+
+    /// Makes the serializer and deserializer report themselves as
+    /// human-readable or compact, instead of panicking when asked.
+    ///
+    /// This is the same as wrapping the value with [`Configure`], without
+    /// having to own the value to wrap it.
+    ///
+    /// [`Configure`]: crate::Configure
+    ///
+    /// ```
+    /// # use serde_test::{assert_tokens_with_config, Config, Token};
+    /// # use std::net::Ipv4Addr;
+    /// #
+    /// let config = Config::new().human_readable(true);
+    /// assert_tokens_with_config(&Ipv4Addr::LOCALHOST, &[Token::Str("127.0.0.1")], config);
+    /// ```
+    #[must_use]
+    pub fn human_readable(mut self, enable: bool) -> Self {
+        self.human_readable = Some(enable);
+        self
+    }
+
+    /// Answers every request from the `Deserialize` impl through
+    /// `deserialize_any`, ignoring the type hint, the way a self-describing
+    /// format that does not look at hints would.
+    ///
+    /// ```
+    /// # use serde_test::{assert_de_tokens_with_config, Config, Token};
+    /// #
+    /// let config = Config::new().self_describing(true);
+    /// assert_de_tokens_with_config(&Some(1u8), &[Token::Some, Token::U8(1)], config);
+    /// ```
+    #[must_use]
+    pub fn self_describing(mut self, enable: bool) -> Self {
+        self.self_describing = enable;
+        self
+    }
+
+    /// Represents byte tokens as base64 strings, the way human-readable
+    /// formats often encode binary data.
+    ///
+    /// When serializing, a string is expected to match a `Bytes`,
+    /// `BorrowedBytes` or `ByteBuf` token if it is the base64 encoding of the
+    /// token's bytes. When deserializing, those tokens are delivered as their
+    /// base64 encoding through `visit_str` or `visit_string`. Requires the
+    /// `bytes-as-base64` feature.
+    ///
+    /// ```
+    /// # use serde_test::{assert_de_tokens_with_config, assert_ser_tokens_with_config, Config, Token};
+    /// #
+    /// let config = Config::new().bytes_as_base64(true);
+    /// let s = "aGVsbG8=".to_owned();
+    /// assert_ser_tokens_with_config(&s, &[Token::Bytes(b"hello")], config.clone());
+    /// assert_de_tokens_with_config(&s, &[Token::Bytes(b"hello")], config);
+    /// ```
+    #[cfg(feature = "bytes-as-base64")]
+    #[must_use]
+    pub fn bytes_as_base64(mut self, enable: bool) -> Self {
+        self.bytes_as_base64 = enable;
+        self
+    }
+
+    /// Selects what happens when the `Serialize` impl emits the same key twice
+    /// within a single map or struct.
+    ///
+    /// Impls that merge several sources into one map are prone to this, and
+    /// the duplicates only blow up later in formats that reject them.
+    ///
+    /// ```should_panic
+    /// # use serde::ser::{Serialize, SerializeMap, Serializer};
+    /// # use serde_test::{assert_ser_tokens_with_config, Config, DuplicateKeys, Token};
+    /// #
+    /// struct Merged;
+    ///
+    /// impl Serialize for Merged {
+    ///     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    ///     where
+    ///         S: Serializer,
+    ///     {
+    ///         let mut map = serializer.serialize_map(None)?;
+    ///         map.serialize_entry("id", &1u8)?;
+    ///         map.serialize_entry("id", &2u8)?;
+    ///         map.end()
+    ///     }
+    /// }
+    ///
+    /// assert_ser_tokens_with_config(
+    ///     &Merged,
+    ///     &[
+    ///         Token::Map { len: None },
+    ///         Token::Str("id"),
+    ///         Token::U8(1),
+    ///         Token::Str("id"),
+    ///         Token::U8(2),
+    ///         Token::MapEnd,
+    ///     ],
+    ///     Config::new().duplicate_keys(DuplicateKeys::Deny),
+    /// );
+    /// ```
+    #[must_use]
+    pub fn duplicate_keys(mut self, duplicate_keys: DuplicateKeys) -> Self {
+        self.duplicate_keys = duplicate_keys;
+        self
+    }
+
+    /// Delivers `BorrowedStr` and `BorrowedBytes` tokens as transient data,
+    /// through `visit_str` and `visit_bytes`, as if the input could not
+    /// outlive the call to the visitor. The borrowed `Visitor` methods are
+    /// never called.
+    ///
+    /// This checks that a type which borrows when it can still deserializes
+    /// from formats that cannot lend data. Unlike [`assert_de_owned`], the
+    /// type need not be `DeserializeOwned`. This option takes precedence
+    /// over [`borrow_bytes`].
+    ///
+    /// [`assert_de_owned`]: crate::assert_de_owned
+    /// [`borrow_bytes`]: Config::borrow_bytes
+    ///
+    /// ```
+    /// # use serde_test::{assert_de_tokens_with_config, Config, Token};
+    /// # use std::borrow::Cow;
+    /// #
+    /// let config = Config::new().owned(true);
+    /// let tokens = [Token::BorrowedStr("borrowed")];
+    ///
+    /// // Deserializes as `Cow::Owned`, which compares equal.
+    /// assert_de_tokens_with_config(&Cow::Borrowed("borrowed"), &tokens, config);
+    /// ```
+    #[must_use]
+    pub fn owned(mut self, enable: bool) -> Self {
+        self.owned = enable;
+        self
+    }
+
+    /// Delivers every `Bytes` and `ByteBuf` token through
+    /// `visit_borrowed_bytes`, as if it were a `BorrowedBytes` token, to
+    /// exercise the zero-copy path of an impl.
+    ///
+    /// The [`Report`] returned by the assertion tells which of the `Visitor`
+    /// methods for bytes the impl accepted.
+    ///
+    /// [`Report`]: crate::Report
+    ///
+    /// ```
+    /// # use serde_test::{assert_de_tokens_with_config, Config, Token};
+    /// #
+    /// let config = Config::new().borrow_bytes(true);
+    /// let tokens = [Token::Bytes(b"abc")];
+    /// let report = assert_de_tokens_with_config(&&b"abc"[..], &tokens, config);
+    /// assert_eq!(report.borrowed_bytes(), 1);
+    /// ```
+    #[must_use]
+    pub fn borrow_bytes(mut self, enable: bool) -> Self {
+        self.borrow_bytes = enable;
+        self
+    }
+
+    /// Restricts the kinds of key the `Serialize` impl may emit for a map.
+    ///
+    /// Formats like JSON and TOML only accept string keys, so a
+    /// `HashMap<u64, T>` or a map keyed by a non-unit enum passes generic
+    /// tests and then fails at runtime.
+    ///
+    /// ```should_panic
+    /// # use serde_test::{assert_ser_tokens_with_config, Config, MapKeys, Token};
+    /// # use std::collections::BTreeMap;
+    /// #
+    /// let mut map = BTreeMap::new();
+    /// map.insert(1u64, 'a');
+    ///
+    /// assert_ser_tokens_with_config(
+    ///     &map,
+    ///     &[
+    ///         Token::Map { len: Some(1) },
+    ///         Token::U64(1),
+    ///         Token::Char('a'),
+    ///         Token::MapEnd,
+    ///     ],
+    ///     Config::new().map_keys(MapKeys::Strings),
+    /// );
+    /// ```
+    #[must_use]
+    pub fn map_keys(mut self, map_keys: MapKeys) -> Self {
+        self.map_keys = map_keys;
+        self
+    }
+
+    /// Registers `predicate` under `name`, for a `Token::Verify(name)` in the
+    /// expected stream to match any scalar the predicate accepts.
+    ///
+    /// Registering a name again replaces the earlier predicate. See
+    /// [`Token::Verify`] for an example.
+    ///
+    /// [`Token::Verify`]: crate::Token::Verify
+    #[must_use]
+    pub fn verify(mut self, name: &'static str, predicate: Predicate) -> Self {
+        self.predicates.push((name, predicate));
+        self
+    }
+
+    /// The predicate registered under `name`, if any.
+    pub(crate) fn predicate(&self, name: &str) -> Option<Predicate> {
+        self.predicates
+            .iter()
+            .rev()
+            .find(|&&(registered, _)| registered == name)
+            .map(|&(_, predicate)| predicate)
+    }
+
+    /// Selects what `SeqAccess::size_hint` reports while deserializing a
+    /// sequence, tuple or tuple struct, regardless of how many element tokens
+    /// follow.
+    ///
+    /// A `Deserialize` impl must not trust the hint: formats that cannot know
+    /// lengths up front report none, and a malicious input can claim any
+    /// length.
+    ///
+    /// ```
+    /// # use serde_test::{assert_de_tokens_with_config, Config, SizeHint, Token};
+    /// #
+    /// // `Vec` caps its preallocation, so an absurd hint does no harm.
+    /// let config = Config::new().seq_size_hint(SizeHint::Fixed(usize::MAX));
+    /// assert_de_tokens_with_config(
+    ///     &vec![1u8, 2],
+    ///     &[
+    ///         Token::Seq { len: Some(2) },
+    ///         Token::U8(1),
+    ///         Token::U8(2),
+    ///         Token::SeqEnd,
+    ///     ],
+    ///     config,
+    /// );
+    /// ```
+    #[must_use]
+    pub fn seq_size_hint(mut self, hint: SizeHint) -> Self {
+        self.seq_size_hint = hint;
+        self
+    }
+
+    /// Selects what `MapAccess::size_hint` reports while deserializing a map
+    /// or struct, regardless of how many entry tokens follow.
+    ///
+    /// `SizeHint::None` mimics formats that stream maps without a length
+    /// prefix, such as JSON.
+    ///
+    /// ```
+    /// # use serde_test::{assert_de_tokens_with_config, Config, SizeHint, Token};
+    /// # use std::collections::BTreeMap;
+    /// #
+    /// let mut map = BTreeMap::new();
+    /// map.insert('a', 1u8);
+    ///
+    /// let config = Config::new().map_size_hint(SizeHint::None);
+    /// assert_de_tokens_with_config(
+    ///     &map,
+    ///     &[
+    ///         Token::Map { len: Some(1) },
+    ///         Token::Char('a'),
+    ///         Token::U8(1),
+    ///         Token::MapEnd,
+    ///     ],
+    ///     config,
+    /// );
+    /// ```
+    #[must_use]
+    pub fn map_size_hint(mut self, hint: SizeHint) -> Self {
+        self.map_size_hint = hint;
+        self
+    }
+
+    /// Fails the assertion if the `len` a `Serialize` impl passes to
+    /// `serialize_seq`, `serialize_tuple`, `serialize_map`, `serialize_struct`
+    /// or their variants differs from the number of elements, entries or
+    /// fields it goes on to serialize.
+    ///
+    /// Self-describing formats ignore the length, but length-prefixed binary
+    /// formats write it up front and are silently corrupted by a wrong one.
+    /// A `len` of `None` is not checked.
+    ///
+    /// ```should_panic
+    /// # use serde::ser::{Serialize, SerializeSeq, Serializer};
+    /// # use serde_test::{assert_ser_tokens_with_config, Config, Token};
+    /// #
+    /// struct Evens(Vec<u8>);
+    ///
+    /// impl Serialize for Evens {
+    ///     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    ///     where
+    ///         S: Serializer,
+    ///     {
+    ///         // Claims every element even though odd ones are left out.
+    ///         let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+    ///         for n in self.0.iter().filter(|n| *n % 2 == 0) {
+    ///             seq.serialize_element(n)?;
+    ///         }
+    ///         seq.end()
+    ///     }
+    /// }
+    ///
+    /// assert_ser_tokens_with_config(
+    ///     &Evens(vec![1, 2]),
+    ///     &[Token::Seq { len: Some(2) }, Token::U8(2), Token::SeqEnd],
+    ///     Config::new().check_len_hints(true),
+    /// );
+    /// ```
+    #[must_use]
+    pub fn check_len_hints(mut self, enable: bool) -> Self {
+        self.check_len_hints = enable;
+        self
+    }
+
+    /// Fails the assertion once the `Serialize` or `Deserialize` impl has
+    /// asked for more than `max_calls` tokens, counting every peek.
+    ///
+    /// A buggy impl that loops forever, for example a visitor that keeps
+    /// asking for the next element after being told there are none, then
+    /// fails the test with a clean panic instead of hanging it. The failure
+    /// is reported like a token mismatch, so [`Output::collect_failures`]
+    /// records it too.
+    ///
+    /// [`Output::collect_failures`]: crate::Output::collect_failures
+    ///
+    /// ```should_panic
+    /// # use serde::de::{Deserialize, Deserializer, SeqAccess, Visitor};
+    /// # use serde_test::{assert_de_tokens_with_config, Config, Token};
+    /// # use std::fmt;
+    /// #
+    /// #[derive(PartialEq, Debug)]
+    /// struct Stubborn;
+    ///
+    /// impl<'de> Deserialize<'de> for Stubborn {
+    ///     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    ///     where
+    ///         D: Deserializer<'de>,
+    ///     {
+    ///         struct StubbornVisitor;
+    ///
+    ///         impl<'de> Visitor<'de> for StubbornVisitor {
+    ///             type Value = Stubborn;
+    ///
+    ///             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    ///                 formatter.write_str("a sequence")
+    ///             }
+    ///
+    ///             fn visit_seq<A>(self, mut seq: A) -> Result<Stubborn, A::Error>
+    ///             where
+    ///                 A: SeqAccess<'de>,
+    ///             {
+    ///                 // Never gives up waiting for an element.
+    ///                 loop {
+    ///                     seq.next_element::<u8>()?;
+    ///                 }
+    ///             }
+    ///         }
+    ///
+    ///         deserializer.deserialize_seq(StubbornVisitor)
+    ///     }
+    /// }
+    ///
+    /// let config = Config::new().max_calls(1000);
+    /// assert_de_tokens_with_config(
+    ///     &Stubborn,
+    ///     &[Token::Seq { len: Some(0) }, Token::SeqEnd],
+    ///     config,
+    /// );
+    /// ```
+    #[must_use]
+    pub fn max_calls(mut self, max_calls: usize) -> Self {
+        self.max_calls = Some(max_calls);
+        self
+    }
+
+    /// Fails the assertion if compound values such as sequences, maps and
+    /// structs are nested more than `max_depth` levels deep.
+    ///
+    /// This catches accidental recursion blowups, such as a wrapper type
+    /// that adds a level of nesting per element, before they overflow the
+    /// stack in a real format. The failure is reported like a token
+    /// mismatch, so [`Output::collect_failures`] records it too.
+    ///
+    /// [`Output::collect_failures`]: crate::Output::collect_failures
+    ///
+    /// ```should_panic
+    /// # use serde_test::{assert_ser_tokens_with_config, Config, Token};
+    /// #
+    /// let nested = vec![vec![vec![0u8]]];
+    /// assert_ser_tokens_with_config(
+    ///     &nested,
+    ///     &[
+    ///         Token::Seq { len: Some(1) },
+    ///         Token::Seq { len: Some(1) },
+    ///         Token::Seq { len: Some(1) },
+    ///         Token::U8(0),
+    ///         Token::SeqEnd,
+    ///         Token::SeqEnd,
+    ///         Token::SeqEnd,
+    ///     ],
+    ///     Config::new().max_depth(2),
+    /// );
+    /// ```
+    #[must_use]
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Fails serialization with "possible cycle while serializing" once
+    /// values are nested more than `max_nesting` deep.
+    ///
+    /// Unlike `max_depth`, every nested value counts, including the content
+    /// of an `Option`, a newtype or a smart pointer's target. A graph built
+    /// from `Rc` whose `Serialize` impl forgets to stop at back-references
+    /// recurses as deep as the expected stream allows; with long generated
+    /// streams that overflows the stack instead of failing the assertion.
+    ///
+    /// ```
+    /// # use serde_derive::Serialize;
+    /// # use serde_test::{assert_ser_tokens_with_config, take_failures, Config, Output, Token};
+    /// # use std::cell::RefCell;
+    /// # use std::rc::Rc;
+    /// #
+    /// #[derive(Serialize)]
+    /// struct Node {
+    ///     next: Option<Rc<RefCell<Node>>>,
+    /// }
+    ///
+    /// let node = Rc::new(RefCell::new(Node { next: None }));
+    /// node.borrow_mut().next = Some(node.clone());
+    ///
+    /// let mut tokens = Vec::new();
+    /// for _ in 0..1000 {
+    ///     tokens.extend_from_slice(&[
+    ///         Token::Struct { name: "Node", len: 1 },
+    ///         Token::Str("next"),
+    ///         Token::Some,
+    ///     ]);
+    /// }
+    ///
+    /// Output::new().collect_failures(true).install();
+    ///
+    /// let config = Config::new().detect_cycles(64);
+    /// assert_ser_tokens_with_config(&node, &tokens, config);
+    ///
+    /// let failures = take_failures();
+    /// assert!(failures[0].message().contains("possible cycle while serializing"));
+    /// ```
+    #[must_use]
+    pub fn detect_cycles(mut self, max_nesting: usize) -> Self {
+        self.detect_cycles = Some(max_nesting);
+        self
+    }
+
+    /// Lets the deserializer leave tokens unconsumed after the value, the way
+    /// streaming formats read one value and leave the rest of the input.
+    ///
+    /// The [`Report`] returned by the assertion tells how many tokens the
+    /// value actually consumed.
+    ///
+    /// [`Report`]: crate::Report
+    ///
+    /// ```
+    /// # use serde_test::{assert_de_tokens_with_config, Config, Token};
+    /// #
+    /// let config = Config::new().allow_trailing(true);
+    /// let tokens = [Token::U8(1), Token::U8(2), Token::U8(3)];
+    /// let report = assert_de_tokens_with_config(&1u8, &tokens, config);
+    /// assert_eq!(report.tokens(), 1);
+    /// ```
+    #[must_use]
+    pub fn allow_trailing(mut self, enable: bool) -> Self {
+        self.allow_trailing = enable;
+        self
+    }
+
+    /// Makes a failing assertion panic with a [`Mismatch`] as the panic
+    /// payload instead of a formatted message.
+    ///
+    /// Harnesses such as fuzzers or custom test runners can then downcast
+    /// the payload caught by `catch_unwind` to inspect the failure. The
+    /// message is still written to stderr, since the default panic hook only
+    /// prints string payloads.
+    ///
+    /// To get the same behavior from every assertion, including the plain
+    /// ones that take no `Config`, install [`Output::mismatch_payload`]
+    /// instead.
+    ///
+    /// [`Mismatch`]: crate::Mismatch
+    /// [`Output::mismatch_payload`]: crate::Output::mismatch_payload
+    #[must_use]
+    pub fn mismatch_payload(mut self, enable: bool) -> Self {
+        self.mismatch_payload = enable;
+        self
+    }
+
+    /// Compares an expected token against an actual one, leaving out struct
+    /// names if `ignore_struct_names` is enabled.
+    pub(crate) fn token_matches(&self, expected: Token, actual: Token) -> bool {
+        if !self.ignore_struct_names {
+            return token_matches(expected, actual);
+        }
+        let expected = match expected {
+            Token::Struct { len, .. } => Token::Struct { name: "*", len },
+            Token::TupleStruct { len, .. } => Token::TupleStruct { name: "*", len },
+            Token::NewtypeStruct { .. } => Token::NewtypeStruct { name: "*" },
+            Token::UnitStruct { .. } => Token::UnitStruct { name: "*" },
+            other => other,
+        };
+        token_matches(expected, actual)
+    }
+
+    /// Compares an expected `f32` against a serialized one within the
+    /// tolerance set by `float_ulps`.
+    #[allow(clippy::cast_possible_wrap)]
+    pub(crate) fn f32_matches(&self, expected: f32, actual: f32) -> bool {
+        if self.float_ulps == 0 || expected.is_nan() || actual.is_nan() {
+            return expected == actual;
+        }
+        let ordered = |v: f32| {
+            let bits = v.to_bits() as i32;
+            if bits < 0 {
+                i64::from(i32::MIN) - i64::from(bits)
+            } else {
+                i64::from(bits)
+            }
+        };
+        (ordered(expected) - ordered(actual)).unsigned_abs() <= self.float_ulps
+    }
+
+    /// Compares an expected `f64` against a serialized one within the
+    /// tolerance set by `float_ulps`.
+    #[allow(clippy::cast_possible_wrap)]
+    pub(crate) fn f64_matches(&self, expected: f64, actual: f64) -> bool {
+        if self.float_ulps == 0 || expected.is_nan() || actual.is_nan() {
+            return expected == actual;
+        }
+        let ordered = |v: f64| {
+            let bits = v.to_bits() as i64;
+            if bits < 0 {
+                i128::from(i64::MIN) - i128::from(bits)
+            } else {
+                i128::from(bits)
+            }
+        };
+        (ordered(expected) - ordered(actual)).unsigned_abs() <= u128::from(self.float_ulps)
+    }
+
+    /// Whether `calls` is over the budget set by `max_calls`, reporting the
+    /// failure at the expected token at `index` the first time it is.
+    ///
+    /// The serializer and deserializer then act as if the tokens had run out,
+    /// so that an impl stuck in a loop errors out even when failures are
+    /// being collected rather than panicking.
+    pub(crate) fn check_calls(&self, calls: usize, index: usize) -> bool {
+        match self.max_calls {
+            Some(max_calls) if calls > max_calls => {
+                if calls == max_calls + 1 {
+                    let message = format!(
+                        "no progress: call budget of {} exceeded, the impl under test may be looping forever",
+                        max_calls,
+                    );
+                    fail(self, &[], Mismatch::at_index(message, index));
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether `depth` is over the limit set by `max_depth`, reporting the
+    /// failure at the expected token at `index` if it is.
+    pub(crate) fn check_depth(&self, depth: usize, index: usize) -> bool {
+        match self.max_depth {
+            Some(max_depth) if depth > max_depth => {
+                let message = format!("maximum nesting depth of {} exceeded", max_depth);
+                fail(self, &[], Mismatch::at_index(message, index));
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The ways in which a deserializer may hand an identifier to a `Deserialize`
+/// impl.
+///
+/// Identifier visitors generated by `serde_derive` accept all of them, and a
+/// hand-written one can be checked against each in turn.
+///
+/// ```
+/// # use serde_derive::Deserialize;
+/// # use serde_test::{assert_de_tokens_with_config, Config, Identifier, Token};
+/// #
+/// #[derive(Deserialize, PartialEq, Debug)]
+/// enum E {
+///     A { x: u8 },
+/// }
+///
+/// let tokens = [
+///     Token::StructVariant { name: "E", variant: "A", len: 1 },
+///     Token::Str("x"),
+///     Token::U8(1),
+///     Token::StructVariantEnd,
+/// ];
+/// for &identifier in &[Identifier::Str, Identifier::Bytes, Identifier::Index] {
+///     let config = Config::new()
+///         .variant_identifiers(identifier)
+///         .field_identifiers(identifier);
+///     assert_de_tokens_with_config(&E::A { x: 1 }, &tokens, config);
+/// }
+/// ```
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Identifier {
+    /// The name, delivered through `visit_str` or one of its relatives.
+    Str,
+    /// The name as UTF-8, delivered through `visit_bytes`.
+    Bytes,
+    /// The position in the list of names known to the `Deserialize` impl,
+    /// delivered through `visit_u64`.
+    Index,
+}
+
+impl Default for Identifier {
+    fn default() -> Self {
+        Identifier::Str
+    }
+}
+
+/// What to do about a key serialized twice in the same map or struct.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DuplicateKeys {
+    /// Accept the duplicate silently.
+    Allow,
+    /// Accept the duplicate after printing a warning to stderr.
+    Warn,
+    /// Fail the assertion.
+    Deny,
+}
+
+impl Default for DuplicateKeys {
+    fn default() -> Self {
+        DuplicateKeys::Allow
+    }
+}
+
+/// The kinds of key allowed in a serialized map.
+#[derive(Copy, Clone, Debug)]
+pub enum MapKeys {
+    /// Any key.
+    Any,
+    /// Only `Str`, `BorrowedStr` and `String` keys, as in JSON and TOML.
+    Strings,
+    /// Only keys that serialize to a single token accepted by the predicate,
+    /// for formats that allow some primitive keys but not others.
+    Only(Predicate),
+}
+
+impl Default for MapKeys {
+    fn default() -> Self {
+        MapKeys::Any
+    }
+}
+
+/// What a `SeqAccess` or `MapAccess` reports as its size hint.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SizeHint {
+    /// The `len` given in the token that starts the value, or none for
+    /// values whose token has no `len`.
+    Token,
+    /// No hint, as from a format that cannot know lengths up front.
+    None,
+    /// The number of elements or entries that actually follow.
+    Exact,
+    /// The given length, whether or not it is true.
+    Fixed(usize),
+}
+
+impl Default for SizeHint {
+    fn default() -> Self {
+        SizeHint::Token
+    }
+}
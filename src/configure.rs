@@ -9,9 +9,9 @@ use serde::ser::{
 use std::fmt::{self, Display};
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub struct Readable<T: ?Sized>(T);
+pub struct Readable<T: ?Sized>(pub(crate) T);
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub struct Compact<T: ?Sized>(T);
+pub struct Compact<T: ?Sized>(pub(crate) T);
 
 /// Trait to determine whether a value is represented in human-readable or
 /// compact form.
@@ -180,6 +180,93 @@ where
     }
 }
 
+/// A value that reveals whether the serializer or deserializer handling it
+/// reports itself as human-readable, matched by
+/// [`Token::AssertHumanReadable`].
+///
+/// Placing probes at different nesting levels of a value checks that a
+/// `Configure`-style wrapper keeps reporting the flag it should while it
+/// recurses into sequences, maps and structs.
+///
+/// [`Token::AssertHumanReadable`]: crate::Token::AssertHumanReadable
+///
+/// ```
+/// # use serde_test::{assert_tokens, Configure, HumanReadableProbe, Token};
+/// #
+/// let value = (HumanReadableProbe, vec![HumanReadableProbe]);
+/// assert_tokens(
+///     &value.compact(),
+///     &[
+///         Token::Tuple { len: 2 },
+///         Token::AssertHumanReadable(false),
+///         Token::Seq { len: Some(1) },
+///         Token::AssertHumanReadable(false),
+///         Token::SeqEnd,
+///         Token::TupleEnd,
+///     ],
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HumanReadableProbe;
+
+const READABLE_PROBE: &str = "$serde_test::HumanReadableProbe(true)";
+const COMPACT_PROBE: &str = "$serde_test::HumanReadableProbe(false)";
+
+fn probe_name(is_human_readable: bool) -> &'static str {
+    if is_human_readable {
+        READABLE_PROBE
+    } else {
+        COMPACT_PROBE
+    }
+}
+
+/// The readability a `HumanReadableProbe` observed, if `name` is the unit
+/// struct name it serializes or deserializes with.
+pub(crate) fn probe_readable(name: &str) -> Option<bool> {
+    match name {
+        READABLE_PROBE => Some(true),
+        COMPACT_PROBE => Some(false),
+        _ => None,
+    }
+}
+
+impl Serialize for HumanReadableProbe {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let name = probe_name(serializer.is_human_readable());
+        serializer.serialize_unit_struct(name)
+    }
+}
+
+impl<'de> Deserialize<'de> for HumanReadableProbe {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ProbeVisitor;
+
+        impl<'de> Visitor<'de> for ProbeVisitor {
+            type Value = HumanReadableProbe;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("Token::AssertHumanReadable")
+            }
+
+            fn visit_unit<E>(self) -> Result<HumanReadableProbe, E>
+            where
+                E: Error,
+            {
+                Ok(HumanReadableProbe)
+            }
+        }
+
+        let name = probe_name(deserializer.is_human_readable());
+        deserializer.deserialize_unit_struct(name, ProbeVisitor)
+    }
+}
+
 macro_rules! forward_method {
     ($name: ident (self $(, $arg: ident : $arg_type: ty)* ) -> $return_type: ty) => {
         fn $name (self $(, $arg : $arg_type)* ) -> $return_type {
@@ -1,59 +1,201 @@
+use crate::config::{Config, Identifier, SizeHint};
+use crate::configure::probe_readable;
 use crate::error::Error;
-use crate::token::Token;
+use crate::path::Path;
+use crate::report::{BytesVisits, Report};
+use crate::token::{closes_compound, name_matches, opens_compound, value_len, Token};
 use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
 use serde::de::{
     self, Deserialize, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
-    VariantAccess, Visitor,
+    Unexpected, VariantAccess, Visitor,
 };
 use serde::forward_to_deserialize_any;
+use std::cell::Cell;
 
+/// A `Deserializer` that answers the calls it receives from a given list of
+/// tokens.
 #[derive(Debug)]
 pub struct Deserializer<'de> {
     tokens: &'de [Token],
+    len: usize,
+    config: Config,
+    calls: Cell<usize>,
+    depth: usize,
+    max_depth: usize,
+    skipped: usize,
+    bytes: BytesVisits,
+    path: Path<'de>,
 }
 
 fn assert_next_token(de: &mut Deserializer, expected: Token) -> Result<(), Error> {
     match de.next_token_opt() {
-        Some(token) if token == expected => Ok(()),
-        Some(other) => Err(de::Error::custom(format!(
-            "expected Token::{} but deserialization wants Token::{}",
-            other, expected,
-        ))),
-        None => Err(de::Error::custom(format!(
-            "end of tokens but deserialization wants Token::{}",
-            expected,
-        ))),
+        Some(token) if de.config.token_matches(token, expected) => Ok(()),
+        Some(other) => Err(Error::mismatch(
+            format!("expected {} but deserialization wants {}", other, expected),
+            de.index() - 1,
+            Some(other),
+            format!("{:?}", expected),
+        )),
+        None => Err(Error::mismatch(
+            format!("end of tokens but deserialization wants {}", expected),
+            de.index(),
+            None,
+            format!("{:?}", expected),
+        )),
     }
 }
 
 fn unexpected(token: Token) -> Error {
-    de::Error::custom(format!(
+    Error::token(format!(
         "deserialization did not expect this token: {}",
         token,
     ))
 }
 
+/// Every integer with a magnitude up to this one is exactly representable as
+/// an `f64`.
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+fn is_safe_i64(v: i64) -> bool {
+    v.unsigned_abs() <= MAX_SAFE_INTEGER
+}
+
+fn is_safe_u64(v: u64) -> bool {
+    v <= MAX_SAFE_INTEGER
+}
+
+/// The name of the variant of a numeric token.
+fn number_kind(token: Token) -> Option<&'static str> {
+    match token {
+        Token::I8(_) => Some("I8"),
+        Token::I16(_) => Some("I16"),
+        Token::I32(_) => Some("I32"),
+        Token::I64(_) => Some("I64"),
+        Token::U8(_) => Some("U8"),
+        Token::U16(_) => Some("U16"),
+        Token::U32(_) => Some("U32"),
+        Token::U64(_) => Some("U64"),
+        Token::F32(_) => Some("F32"),
+        Token::F64(_) => Some("F64"),
+        _ => None,
+    }
+}
+
+macro_rules! deserialize_number {
+    ($($method:ident => $kind:ident,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+            where
+                V: Visitor<'de>,
+            {
+                self.check_number_kind(stringify!($kind))?;
+                self.deserialize_any(visitor)
+            }
+        )*
+    };
+}
+
+fn is_unordered_marker(token: Token) -> bool {
+    matches!(token, Token::UnorderedStart | Token::UnorderedEnd)
+}
+
+/// The number of complete values in `tokens` before `end`.
+fn count_values(mut tokens: &[Token], end: Token) -> usize {
+    let mut count = 0;
+    loop {
+        match tokens.first() {
+            Some(&token) if is_unordered_marker(token) => tokens = &tokens[1..],
+            Some(&token) if token != end => {
+                tokens = &tokens[value_len(tokens)..];
+                count += 1;
+            }
+            _ => return count,
+        }
+    }
+}
+
 fn end_of_tokens() -> Error {
-    de::Error::custom("ran out of tokens to deserialize")
+    Error::token("ran out of tokens to deserialize")
 }
 
 impl<'de> Deserializer<'de> {
+    /// Creates the deserializer.
+    #[must_use]
     pub fn new(tokens: &'de [Token]) -> Self {
-        Deserializer { tokens }
+        Deserializer::with_config(tokens, Config::new())
+    }
+
+    /// Creates the deserializer with the options in `config`.
+    #[must_use]
+    pub fn with_config(tokens: &'de [Token], config: Config) -> Self {
+        Deserializer {
+            tokens,
+            len: tokens.len(),
+            config,
+            calls: Cell::new(0),
+            depth: 0,
+            max_depth: 0,
+            skipped: 0,
+            bytes: BytesVisits::default(),
+            path: Path::with_capacity(tokens.len()),
+        }
+    }
+
+    /// Creates a deserializer that delivers borrowed tokens as transient
+    /// data, as if the input could not outlive the call to the visitor.
+    #[must_use]
+    pub fn owned(tokens: &'de [Token]) -> Self {
+        let config = Config {
+            owned: true,
+            ..Config::new()
+        };
+        Deserializer::with_config(tokens, config)
+    }
+
+    /// Counts one more request for a token against the call budget, and
+    /// returns whether the budget is spent.
+    fn tick(&self) -> bool {
+        let calls = self.calls.get() + 1;
+        self.calls.set(calls);
+        self.config.check_calls(calls, self.index())
     }
 
     fn peek_token_opt(&self) -> Option<Token> {
-        self.tokens.first().copied()
+        if self.tick() {
+            return None;
+        }
+        self.tokens
+            .iter()
+            .copied()
+            .find(|&token| !is_unordered_marker(token))
+    }
+
+    /// Steps over the markers of unordered groups, which are only meaningful
+    /// when serializing.
+    fn skip_unordered_markers(&mut self) {
+        while let Some(&token) = self.tokens.first() {
+            if !is_unordered_marker(token) {
+                break;
+            }
+            self.tokens = &self.tokens[1..];
+        }
     }
 
     fn peek_token(&self) -> Result<Token, Error> {
         self.peek_token_opt().ok_or_else(end_of_tokens)
     }
 
+    /// Takes the next token, if any are left.
     pub fn next_token_opt(&mut self) -> Option<Token> {
+        if self.tick() {
+            self.tokens = &[];
+            return None;
+        }
+        self.skip_unordered_markers();
         match self.tokens.split_first() {
             Some((&first, rest)) => {
                 self.tokens = rest;
+                self.track_depth(first);
                 Some(first)
             }
             None => None,
@@ -61,15 +203,134 @@ impl<'de> Deserializer<'de> {
     }
 
     fn next_token(&mut self) -> Result<Token, Error> {
+        if self.tick() {
+            self.tokens = &[];
+            return Err(end_of_tokens());
+        }
+        self.skip_unordered_markers();
         let (&first, rest) = self.tokens.split_first().ok_or_else(end_of_tokens)?;
         self.tokens = rest;
+        self.track_depth(first);
         Ok(first)
     }
 
+    /// The number of tokens not yet consumed.
     pub fn remaining(&self) -> usize {
         self.tokens.len()
     }
 
+    /// The position of the next token in the expected stream.
+    pub fn index(&self) -> usize {
+        self.len - self.tokens.len()
+    }
+
+    /// The deepest level of nested compound values consumed so far.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Statistics about the tokens consumed so far.
+    pub fn report(&self) -> Report {
+        Report {
+            tokens: self.index(),
+            max_depth: self.max_depth,
+            skipped: self.skipped,
+            bytes: self.bytes,
+        }
+    }
+
+    fn track_depth(&mut self, token: Token) {
+        if opens_compound(token) {
+            self.depth += 1;
+            self.max_depth = self.max_depth.max(self.depth);
+            if self.config.check_depth(self.depth, self.index() - 1) {
+                // Nothing deeper is matched once the failure is reported.
+                self.tokens = &[];
+            }
+        } else if closes_compound(token) {
+            self.depth = self.depth.saturating_sub(1);
+        }
+    }
+
+    /// Takes the string token holding a 128-bit integer, if 128-bit integers
+    /// are represented as strings.
+    fn int128_str(&mut self) -> Result<Option<&'static str>, Error> {
+        if !self.config.int128_as_strings {
+            return Ok(None);
+        }
+        match self.peek_token()? {
+            Token::Str(s) | Token::BorrowedStr(s) | Token::String(s) => {
+                self.next_token()?;
+                Ok(Some(s))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Fails if numeric kinds must match exactly and the next token is a
+    /// number of a different kind than the one requested.
+    fn check_number_kind(&self, wanted: &'static str) -> Result<(), Error> {
+        if !self.config.exact_numbers {
+            return Ok(());
+        }
+        let token = match self.peek_token_opt() {
+            Some(token) => token,
+            None => return Ok(()),
+        };
+        match number_kind(token) {
+            Some(kind) if kind != wanted => Err(Error::token(format!(
+                "expected {} but deserialization wants Token::{}",
+                token, wanted,
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Delivers the bytes of a `Bytes`, `BorrowedBytes` or `ByteBuf` token
+    /// through the matching `Visitor` method, counting the visit if the impl
+    /// accepts it.
+    fn visit_bytes_token<V>(
+        &mut self,
+        token: Token,
+        v: &'static [u8],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let borrowed = match token {
+            _ if self.config.owned => false,
+            Token::BorrowedBytes(_) => true,
+            _ => self.config.borrow_bytes,
+        };
+        if borrowed {
+            let value = visitor.visit_borrowed_bytes(v)?;
+            self.bytes.borrowed += 1;
+            Ok(value)
+        } else if let Token::ByteBuf(_) = token {
+            let value = visitor.visit_byte_buf(v.to_vec())?;
+            self.bytes.owned += 1;
+            Ok(value)
+        } else {
+            let value = visitor.visit_bytes(v)?;
+            self.bytes.transient += 1;
+            Ok(value)
+        }
+    }
+
+    /// Deserializes a value at the path the caller has just extended, then
+    /// leaves that part of the path again.
+    fn deserialize_in<T>(&mut self, seed: T) -> Result<T::Value, Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let result = seed
+            .deserialize(&mut *self)
+            .map_err(|err| err.at(&self.path));
+        self.path.pop();
+        result
+    }
+
     fn visit_seq<V>(
         &mut self,
         len: Option<usize>,
@@ -79,21 +340,52 @@ impl<'de> Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        let value = visitor.visit_seq(DeserializerSeqVisitor { de: self, len, end })?;
+        let len = self.size_hint(self.config.seq_size_hint, len, end, 1);
+        let value = visitor.visit_seq(DeserializerSeqVisitor {
+            de: self,
+            len,
+            end,
+            index: 0,
+        })?;
         assert_next_token(self, end)?;
         Ok(value)
     }
 
+    /// The size hint to report for a compound value whose header gave `len`,
+    /// where each entry consists of `per_entry` values.
+    fn size_hint(
+        &self,
+        hint: SizeHint,
+        len: Option<usize>,
+        end: Token,
+        per_entry: usize,
+    ) -> Option<usize> {
+        match hint {
+            SizeHint::Token => len,
+            SizeHint::None => None,
+            SizeHint::Exact => Some(count_values(self.tokens, end) / per_entry),
+            SizeHint::Fixed(len) => Some(len),
+        }
+    }
+
     fn visit_map<V>(
         &mut self,
         len: Option<usize>,
+        fields: &'static [&'static str],
         end: Token,
         visitor: V,
     ) -> Result<V::Value, Error>
     where
         V: Visitor<'de>,
     {
-        let value = visitor.visit_map(DeserializerMapVisitor { de: self, len, end })?;
+        let len = self.size_hint(self.config.map_size_hint, len, end, 2);
+        let value = visitor.visit_map(DeserializerMapVisitor {
+            de: self,
+            len,
+            fields,
+            end,
+            key: &[],
+        })?;
         assert_next_token(self, end)?;
         Ok(value)
     }
@@ -103,8 +395,60 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     type Error = Error;
 
     forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
-        bytes byte_buf unit seq map identifier ignored_any
+        bool char str string bytes byte_buf unit seq map identifier
+    }
+
+    deserialize_number! {
+        deserialize_i8 => I8,
+        deserialize_i16 => I16,
+        deserialize_i32 => I32,
+        deserialize_i64 => I64,
+        deserialize_u8 => U8,
+        deserialize_u16 => U16,
+        deserialize_u32 => U32,
+        deserialize_u64 => U64,
+        deserialize_f32 => F32,
+        deserialize_f64 => F64,
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.skipped += 1;
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
+        match self.int128_str()? {
+            Some(s) => match s.parse() {
+                Ok(v) => visitor.visit_i128(v),
+                Err(_) => Err(de::Error::invalid_value(Unexpected::Str(s), &"an i128")),
+            },
+            None => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
+        match self.int128_str()? {
+            Some(s) => match s.parse() {
+                Ok(v) => visitor.visit_u128(v),
+                Err(_) => Err(de::Error::invalid_value(Unexpected::Str(s), &"a u128")),
+            },
+            None => self.deserialize_any(visitor),
+        }
     }
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
@@ -113,24 +457,41 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     {
         let token = self.next_token()?;
         match token {
+            #[cfg(feature = "bytes-as-base64")]
+            Token::Bytes(v) | Token::BorrowedBytes(v) if self.config.bytes_as_base64 => {
+                visitor.visit_str(&crate::base64::encode(v))
+            }
+            #[cfg(feature = "bytes-as-base64")]
+            Token::ByteBuf(v) if self.config.bytes_as_base64 => {
+                visitor.visit_string(crate::base64::encode(v))
+            }
             Token::Bool(v) => visitor.visit_bool(v),
             Token::I8(v) => visitor.visit_i8(v),
             Token::I16(v) => visitor.visit_i16(v),
             Token::I32(v) => visitor.visit_i32(v),
+            Token::I64(v) if self.config.large_integers_as_strings && !is_safe_i64(v) => {
+                visitor.visit_str(&v.to_string())
+            }
             Token::I64(v) => visitor.visit_i64(v),
             Token::U8(v) => visitor.visit_u8(v),
             Token::U16(v) => visitor.visit_u16(v),
             Token::U32(v) => visitor.visit_u32(v),
+            Token::VariantIndex(v) => visitor.visit_u32(v),
+            Token::U64(v) if self.config.large_integers_as_strings && !is_safe_u64(v) => {
+                visitor.visit_str(&v.to_string())
+            }
             Token::U64(v) => visitor.visit_u64(v),
             Token::F32(v) => visitor.visit_f32(v),
             Token::F64(v) => visitor.visit_f64(v),
             Token::Char(v) => visitor.visit_char(v),
-            Token::Str(v) => visitor.visit_str(v),
+            Token::Str(v) | Token::CollectStr(v) => visitor.visit_str(v),
+            Token::BorrowedStr(v) if self.config.owned => visitor.visit_str(v),
             Token::BorrowedStr(v) => visitor.visit_borrowed_str(v),
             Token::String(v) => visitor.visit_string(v.to_owned()),
-            Token::Bytes(v) => visitor.visit_bytes(v),
-            Token::BorrowedBytes(v) => visitor.visit_borrowed_bytes(v),
-            Token::ByteBuf(v) => visitor.visit_byte_buf(v.to_vec()),
+            Token::Bytes(v) | Token::BorrowedBytes(v) | Token::ByteBuf(v) => {
+                self.visit_bytes_token(token, v, visitor)
+            }
+            Token::BytesIdentifier(v) => visitor.visit_bytes(v.as_bytes()),
             Token::None => visitor.visit_none(),
             Token::Some => visitor.visit_some(self),
             Token::Unit | Token::UnitStruct { .. } => visitor.visit_unit(),
@@ -140,8 +501,8 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
             Token::TupleStruct { len, .. } => {
                 self.visit_seq(Some(len), Token::TupleStructEnd, visitor)
             }
-            Token::Map { len } => self.visit_map(len, Token::MapEnd, visitor),
-            Token::Struct { len, .. } => self.visit_map(Some(len), Token::StructEnd, visitor),
+            Token::Map { len } => self.visit_map(len, &[], Token::MapEnd, visitor),
+            Token::Struct { len, .. } => self.visit_map(Some(len), &[], Token::StructEnd, visitor),
             Token::Enum { .. } => {
                 let variant = self.next_token()?;
                 let next = self.peek_token()?;
@@ -152,7 +513,11 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
                     }
                     (Token::BorrowedStr(variant), Token::Unit) => {
                         self.next_token()?;
-                        visitor.visit_borrowed_str(variant)
+                        if self.config.owned {
+                            visitor.visit_str(variant)
+                        } else {
+                            visitor.visit_borrowed_str(variant)
+                        }
                     }
                     (Token::String(variant), Token::Unit) => {
                         self.next_token()?;
@@ -164,7 +529,11 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
                     }
                     (Token::BorrowedBytes(variant), Token::Unit) => {
                         self.next_token()?;
-                        visitor.visit_borrowed_bytes(variant)
+                        if self.config.owned {
+                            visitor.visit_bytes(variant)
+                        } else {
+                            visitor.visit_borrowed_bytes(variant)
+                        }
                     }
                     (Token::ByteBuf(variant), Token::Unit) => {
                         self.next_token()?;
@@ -214,7 +583,19 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
             | Token::MapEnd
             | Token::StructEnd
             | Token::TupleVariantEnd
-            | Token::StructVariantEnd => Err(unexpected(token)),
+            | Token::StructVariantEnd
+            | Token::UnorderedStart
+            | Token::UnorderedEnd => Err(unexpected(token)),
+            Token::Any | Token::AnyNumber | Token::AnyStr | Token::SkipValue | Token::Verify(_) => {
+                Err(Error::token(format_args!(
+                    "{} only matches during serialization and cannot be deserialized",
+                    token,
+                )))
+            }
+            Token::Error(msg) => Err(de::Error::custom(msg)),
+            Token::AssertHumanReadable(_) => Err(Error::token(
+                "Token::AssertHumanReadable only matches a HumanReadableProbe",
+            )),
         }
     }
 
@@ -222,6 +603,9 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
         match self.peek_token()? {
             Token::Unit | Token::None => {
                 self.next_token()?;
@@ -238,25 +622,29 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     fn deserialize_enum<V>(
         self,
         name: &'static str,
-        _variants: &'static [&'static str],
+        variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value, Error>
     where
         V: Visitor<'de>,
     {
+        if self.config.enums_as_maps || self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
+
         match self.peek_token()? {
-            Token::Enum { name: n } if name == n => {
+            Token::Enum { name: n } if name_matches(n, name) => {
                 self.next_token()?;
 
-                visitor.visit_enum(DeserializerEnumVisitor { de: self })
+                visitor.visit_enum(DeserializerEnumVisitor { de: self, variants })
             }
             Token::UnitVariant { name: n, .. }
             | Token::NewtypeVariant { name: n, .. }
             | Token::TupleVariant { name: n, .. }
             | Token::StructVariant { name: n, .. }
-                if name == n =>
+                if name_matches(n, name) =>
             {
-                visitor.visit_enum(DeserializerEnumVisitor { de: self })
+                visitor.visit_enum(DeserializerEnumVisitor { de: self, variants })
             }
             _ => self.deserialize_any(visitor),
         }
@@ -266,6 +654,13 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
+        if let Some(readable) = probe_readable(name) {
+            assert_next_token(self, Token::AssertHumanReadable(readable))?;
+            return visitor.visit_unit();
+        }
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
         match self.peek_token()? {
             Token::UnitStruct { .. } => {
                 assert_next_token(self, Token::UnitStruct { name })?;
@@ -283,6 +678,9 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
         match self.peek_token()? {
             Token::NewtypeStruct { .. } => {
                 assert_next_token(self, Token::NewtypeStruct { name })?;
@@ -296,6 +694,9 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
         match self.peek_token()? {
             Token::Unit | Token::UnitStruct { .. } => {
                 self.next_token()?;
@@ -326,6 +727,9 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
         match self.peek_token()? {
             Token::Unit => {
                 self.next_token()?;
@@ -360,20 +764,26 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
         match self.peek_token()? {
             Token::Struct { len: n, .. } => {
                 assert_next_token(self, Token::Struct { name, len: n })?;
-                self.visit_map(Some(fields.len()), Token::StructEnd, visitor)
+                self.visit_map(Some(fields.len()), fields, Token::StructEnd, visitor)
             }
             Token::Map { .. } => {
                 self.next_token()?;
-                self.visit_map(Some(fields.len()), Token::MapEnd, visitor)
+                self.visit_map(Some(fields.len()), fields, Token::MapEnd, visitor)
             }
             _ => self.deserialize_any(visitor),
         }
     }
 
     fn is_human_readable(&self) -> bool {
+        if let Some(readable) = self.config.human_readable {
+            return readable;
+        }
         panic!(
             "Types which have different human-readable and compact representations \
              must explicitly mark their test cases with `serde_test::Configure`"
@@ -387,6 +797,7 @@ struct DeserializerSeqVisitor<'a, 'de: 'a> {
     de: &'a mut Deserializer<'de>,
     len: Option<usize>,
     end: Token,
+    index: usize,
 }
 
 impl<'de, 'a> SeqAccess<'de> for DeserializerSeqVisitor<'a, 'de> {
@@ -400,7 +811,9 @@ impl<'de, 'a> SeqAccess<'de> for DeserializerSeqVisitor<'a, 'de> {
             return Ok(None);
         }
         self.len = self.len.map(|len| len.saturating_sub(1));
-        seed.deserialize(&mut *self.de).map(Some)
+        self.de.path.push_index(self.index);
+        self.index += 1;
+        self.de.deserialize_in(seed).map(Some)
     }
 
     fn size_hint(&self) -> Option<usize> {
@@ -413,7 +826,30 @@ impl<'de, 'a> SeqAccess<'de> for DeserializerSeqVisitor<'a, 'de> {
 struct DeserializerMapVisitor<'a, 'de: 'a> {
     de: &'a mut Deserializer<'de>,
     len: Option<usize>,
+    /// The fields of the struct being deserialized, or empty for a map.
+    fields: &'static [&'static str],
     end: Token,
+    /// The tokens of the most recent key.
+    key: &'de [Token],
+}
+
+impl<'a, 'de> DeserializerMapVisitor<'a, 'de> {
+    /// Converts a field name into the identifier the configuration asks for.
+    fn field_identifier<K>(&self, field: &'static str, seed: K) -> Result<K::Value, Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.de.config.field_identifiers {
+            Identifier::Str => seed.deserialize(field.into_deserializer()),
+            Identifier::Bytes => seed.deserialize(BytesDeserializer {
+                value: field.as_bytes(),
+            }),
+            Identifier::Index => match self.fields.iter().position(|&f| f == field) {
+                Some(index) => seed.deserialize((index as u64).into_deserializer()),
+                None => Err(de::Error::unknown_field(field, self.fields)),
+            },
+        }
+    }
 }
 
 impl<'de, 'a> MapAccess<'de> for DeserializerMapVisitor<'a, 'de> {
@@ -427,14 +863,35 @@ impl<'de, 'a> MapAccess<'de> for DeserializerMapVisitor<'a, 'de> {
             return Ok(None);
         }
         self.len = self.len.map(|len| len.saturating_sub(1));
-        seed.deserialize(&mut *self.de).map(Some)
+        let before = self.de.tokens;
+        let key = match self.de.peek_token()? {
+            Token::Str(field) | Token::BorrowedStr(field) | Token::String(field)
+                if self.de.config.field_identifiers != Identifier::Str
+                    && !self.fields.is_empty() =>
+            {
+                self.de.next_token()?;
+                self.field_identifier(field, seed)?
+            }
+            _ => seed.deserialize(&mut *self.de)?,
+        };
+        self.key = &before[..before.len() - self.de.tokens.len()];
+        Ok(Some(key))
     }
 
     fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Error>
     where
         V: DeserializeSeed<'de>,
     {
-        seed.deserialize(&mut *self.de)
+        match (self.end, self.key) {
+            (
+                Token::StructEnd | Token::StructVariantEnd,
+                [Token::Str(field) | Token::BorrowedStr(field)],
+            ) => {
+                self.de.path.push_field(field);
+            }
+            _ => self.de.path.push_key(self.key),
+        }
+        self.de.deserialize_in(seed)
     }
 
     fn size_hint(&self) -> Option<usize> {
@@ -446,6 +903,27 @@ impl<'de, 'a> MapAccess<'de> for DeserializerMapVisitor<'a, 'de> {
 
 struct DeserializerEnumVisitor<'a, 'de: 'a> {
     de: &'a mut Deserializer<'de>,
+    variants: &'static [&'static str],
+}
+
+impl<'a, 'de> DeserializerEnumVisitor<'a, 'de> {
+    /// Converts a variant name into the identifier the configuration asks
+    /// for.
+    fn variant_identifier<V>(&self, variant: &'static str, seed: V) -> Result<V::Value, Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.de.config.variant_identifiers {
+            Identifier::Str => seed.deserialize(variant.into_deserializer()),
+            Identifier::Bytes => seed.deserialize(BytesDeserializer {
+                value: variant.as_bytes(),
+            }),
+            Identifier::Index => match self.variants.iter().position(|&v| v == variant) {
+                Some(index) => seed.deserialize((index as u64).into_deserializer()),
+                None => Err(de::Error::unknown_variant(variant, self.variants)),
+            },
+        }
+    }
 }
 
 impl<'de, 'a> EnumAccess<'de> for DeserializerEnumVisitor<'a, 'de> {
@@ -461,8 +939,19 @@ impl<'de, 'a> EnumAccess<'de> for DeserializerEnumVisitor<'a, 'de> {
             | Token::NewtypeVariant { variant: v, .. }
             | Token::TupleVariant { variant: v, .. }
             | Token::StructVariant { variant: v, .. } => {
-                let de = v.into_deserializer();
-                let value = seed.deserialize(de)?;
+                let value = self.variant_identifier(v, seed)?;
+                Ok((value, self))
+            }
+            Token::VariantIndex(index) => {
+                self.de.next_token()?;
+                let value = seed.deserialize(index.into_deserializer())?;
+                Ok((value, self))
+            }
+            Token::Str(v) | Token::BorrowedStr(v) | Token::String(v)
+                if self.de.config.variant_identifiers != Identifier::Str =>
+            {
+                self.de.next_token()?;
+                let value = self.variant_identifier(v, seed)?;
                 Ok((value, self))
             }
             _ => {
@@ -543,7 +1032,7 @@ impl<'de, 'a> VariantAccess<'de> for DeserializerEnumVisitor<'a, 'de> {
 
                 if fields.len() == enum_len {
                     self.de
-                        .visit_map(Some(fields.len()), Token::StructVariantEnd, visitor)
+                        .visit_map(Some(fields.len()), fields, Token::StructVariantEnd, visitor)
                 } else {
                     Err(unexpected(token))
                 }
@@ -555,7 +1044,7 @@ impl<'de, 'a> VariantAccess<'de> for DeserializerEnumVisitor<'a, 'de> {
 
                 if fields.len() == enum_len {
                     self.de
-                        .visit_map(Some(fields.len()), Token::MapEnd, visitor)
+                        .visit_map(Some(fields.len()), fields, Token::MapEnd, visitor)
                 } else {
                     Err(unexpected(token))
                 }
@@ -614,10 +1103,17 @@ impl<'de, 'a> MapAccess<'de> for EnumMapVisitor<'a, 'de> {
         match self.format {
             EnumFormat::Seq => {
                 let value = {
+                    let len = self.de.size_hint(
+                        self.de.config.seq_size_hint,
+                        None,
+                        Token::TupleVariantEnd,
+                        1,
+                    );
                     let visitor = DeserializerSeqVisitor {
                         de: self.de,
-                        len: None,
+                        len,
                         end: Token::TupleVariantEnd,
+                        index: 0,
                     };
                     seed.deserialize(SeqAccessDeserializer::new(visitor))?
                 };
@@ -626,10 +1122,18 @@ impl<'de, 'a> MapAccess<'de> for EnumMapVisitor<'a, 'de> {
             }
             EnumFormat::Map => {
                 let value = {
+                    let len = self.de.size_hint(
+                        self.de.config.map_size_hint,
+                        None,
+                        Token::StructVariantEnd,
+                        2,
+                    );
                     let visitor = DeserializerMapVisitor {
                         de: self.de,
-                        len: None,
+                        len,
+                        fields: &[],
                         end: Token::StructVariantEnd,
+                        key: &[],
                     };
                     seed.deserialize(MapAccessDeserializer::new(visitor))?
                 };
@@ -641,6 +1145,30 @@ impl<'de, 'a> MapAccess<'de> for EnumMapVisitor<'a, 'de> {
     }
 }
 
+/// A `Deserializer` that fails every request with the `expecting` message of
+/// the visitor it was handed.
+pub struct ExpectingDeserializer;
+
+impl<'de> de::Deserializer<'de> for ExpectingDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(de::Error::custom(format_args!(
+            "{}",
+            &visitor as &dyn de::Expected
+        )))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
 struct BytesDeserializer {
     value: &'static [u8],
 }
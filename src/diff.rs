@@ -0,0 +1,141 @@
+use std::fmt::Write;
+
+/// Bytes shown per row of a hexdump.
+const ROW: usize = 16;
+
+/// Rows shown on either side of the row holding the first difference.
+const CONTEXT_ROWS: usize = 2;
+
+/// Renders `expected` and `actual` as interleaved hexdumps with ASCII
+/// gutters, with a caret under the first byte at which they differ.
+///
+/// Only the rows around the first difference are shown.
+pub(crate) fn hexdump(expected: &[u8], actual: &[u8]) -> String {
+    let offset = expected
+        .iter()
+        .zip(actual)
+        .position(|(e, a)| e != a)
+        .unwrap_or_else(|| expected.len().min(actual.len()));
+
+    let mut out = format!(
+        "first difference at offset {} (expected {} bytes, actual {})",
+        offset,
+        expected.len(),
+        actual.len(),
+    );
+    let rows = (expected.len().max(actual.len()) + ROW - 1) / ROW;
+    let diff_row = offset / ROW;
+    let first = diff_row.saturating_sub(CONTEXT_ROWS);
+    let last = rows.min(diff_row + CONTEXT_ROWS + 1);
+    if first > 0 {
+        out.push_str("\n  ...");
+    }
+    for row in first..last {
+        out.push('\n');
+        hexdump_row(&mut out, "expected", expected, row);
+        out.push('\n');
+        hexdump_row(&mut out, "  actual", actual, row);
+        if row == diff_row {
+            let column = offset % ROW;
+            let pad = 19 + column * 3 + usize::from(column >= ROW / 2);
+            let _ = write!(out, "\n{:pad$}^^", "", pad = pad);
+        }
+    }
+    if last < rows {
+        out.push_str("\n  ...");
+    }
+    out
+}
+
+fn hexdump_row(out: &mut String, label: &str, bytes: &[u8], row: usize) {
+    let start = (row * ROW).min(bytes.len());
+    let end = (start + ROW).min(bytes.len());
+    let chunk = &bytes[start..end];
+
+    let _ = write!(out, "{} {:08x}  ", label, row * ROW);
+    for i in 0..ROW {
+        if i == ROW / 2 {
+            out.push(' ');
+        }
+        match chunk.get(i) {
+            Some(byte) => {
+                let _ = write!(out, "{:02x} ", byte);
+            }
+            None => out.push_str("   "),
+        }
+    }
+    out.push('|');
+    for &byte in chunk {
+        out.push(if byte.is_ascii_graphic() || byte == b' ' {
+            byte as char
+        } else {
+            '.'
+        });
+    }
+    out.push('|');
+}
+
+/// Strings at least this many characters long are diffed rather than printed
+/// in full.
+pub(crate) const LONG_STRING: usize = 80;
+
+/// Characters of common text shown on either side of the differing span.
+const CONTEXT_CHARS: usize = 20;
+
+/// Renders the span at which `expected` and `actual` differ, eliding the bulk
+/// of their common prefix and suffix and marking the span with brackets.
+pub(crate) fn string_diff(expected: &str, actual: &str) -> String {
+    let prefix = expected
+        .char_indices()
+        .zip(actual.chars())
+        .find(|&((_, e), a)| e != a)
+        .map_or_else(|| expected.len().min(actual.len()), |((i, _), _)| i);
+    let (expected_rest, actual_rest) = (&expected[prefix..], &actual[prefix..]);
+    let suffix = expected_rest
+        .chars()
+        .rev()
+        .zip(actual_rest.chars().rev())
+        .take_while(|(e, a)| e == a)
+        .map(|(e, _)| e.len_utf8())
+        .sum::<usize>();
+
+    let before = &expected[..prefix];
+    let after = &expected_rest[expected_rest.len() - suffix..];
+    let (before_elided, before) = tail(before, CONTEXT_CHARS);
+    let (after, after_elided) = head(after, CONTEXT_CHARS);
+    let line = |text: &str| {
+        format!(
+            "{}{}[{}]{}{}",
+            if before_elided { "..." } else { "" },
+            before.escape_debug(),
+            text.escape_debug(),
+            after.escape_debug(),
+            if after_elided { "..." } else { "" },
+        )
+    };
+
+    format!(
+        "first difference at char {} (expected {} chars, actual {})\nexpected: {}\n  actual: {}",
+        expected[..prefix].chars().count(),
+        expected.chars().count(),
+        actual.chars().count(),
+        line(&expected_rest[..expected_rest.len() - suffix]),
+        line(&actual_rest[..actual_rest.len() - suffix]),
+    )
+}
+
+/// The last `n` characters of `s`, and whether any were cut off.
+fn tail(s: &str, n: usize) -> (bool, &str) {
+    match s.char_indices().rev().nth(n.saturating_sub(1)) {
+        Some((i, _)) if n > 0 => (i > 0, &s[i..]),
+        _ => (false, s),
+    }
+}
+
+/// The first `n` characters of `s`, and whether any were cut off.
+fn head(s: &str, n: usize) -> (&str, bool) {
+    match s.char_indices().nth(n) {
+        Some((i, _)) => (&s[..i], true),
+        None => (s, false),
+    }
+}
@@ -1,16 +1,180 @@
-use serde::{de, ser};
+use crate::path::Path;
+use crate::token::Token;
+use serde::de;
+use serde::ser;
 use std::error;
-use std::fmt::{self, Display};
+use std::fmt::{self, Debug, Display};
 
+/// The error produced by the token [`Serializer`] and [`Deserializer`].
+///
+/// [`Serializer`]: crate::raw::Serializer
+/// [`Deserializer`]: crate::raw::Deserializer
 #[derive(Clone, Debug)]
 pub struct Error {
     msg: String,
+    pub(crate) detail: Box<Detail>,
+}
+
+/// Where an error happened and what it was about, when known.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Detail {
+    pub(crate) kind: ErrorKind,
+    pub(crate) unexpected: Option<String>,
+    pub(crate) index: Option<usize>,
+    pub(crate) expected: Option<Token>,
+    pub(crate) actual: Option<String>,
+    pub(crate) path: Option<String>,
+}
+
+impl Error {
+    /// An error for the token at `index` of the expected stream not matching
+    /// what the impl under test produced or asked for.
+    pub(crate) fn mismatch(
+        msg: String,
+        index: usize,
+        expected: Option<Token>,
+        actual: String,
+    ) -> Self {
+        Error {
+            msg,
+            detail: Box::new(Detail {
+                kind: ErrorKind::TokenMismatch,
+                unexpected: None,
+                index: Some(index),
+                expected,
+                actual: Some(actual),
+                path: None,
+            }),
+        }
+    }
+
+    /// An error about the token stream as a whole, such as running out of
+    /// tokens, rather than about one expected token.
+    pub(crate) fn token<T: Display>(msg: T) -> Self {
+        Error {
+            msg: msg.to_string(),
+            detail: Box::new(Detail {
+                kind: ErrorKind::TokenMismatch,
+                ..Detail::default()
+            }),
+        }
+    }
+
+    /// Attributes the error to `path`, unless it was already attributed to a
+    /// more deeply nested value.
+    pub(crate) fn at(mut self, path: &Path) -> Self {
+        if self.detail.path.is_none() && !path.is_empty() {
+            self.detail.path = Some(path.to_string());
+        }
+        self
+    }
+
+    /// The location in the data model of the value being serialized or
+    /// deserialized when the error happened, such as `servers[3].port`.
+    #[must_use]
+    pub fn path(&self) -> Option<&str> {
+        self.detail.path.as_deref()
+    }
+
+    /// The category of the error.
+    #[must_use]
+    pub fn kind(&self) -> ErrorKind {
+        self.detail.kind
+    }
+
+    /// The `Unexpected` value an invalid type or invalid value error was
+    /// about, as it is displayed, such as `string "x"` or ``integer `-1` ``.
+    #[must_use]
+    pub fn unexpected(&self) -> Option<&str> {
+        self.detail.unexpected.as_deref()
+    }
+
+    /// An error of the given `kind`, with the message serde would produce for
+    /// it.
+    fn with_kind(message: Message, kind: ErrorKind, unexpected: Option<String>) -> Self {
+        Error {
+            msg: message.0,
+            detail: Box::new(Detail {
+                kind,
+                unexpected,
+                ..Detail::default()
+            }),
+        }
+    }
+}
+
+/// The categories of error that a `Deserialize` impl or the token
+/// deserializer can produce.
+///
+/// Matching on the category rather than the message keeps negative tests
+/// from depending on the exact wording of serde's error messages. New
+/// categories may be added in minor releases.
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// An error made with `Error::custom`.
+    Custom,
+    /// An error made with `Error::invalid_type`.
+    InvalidType,
+    /// An error made with `Error::invalid_value`.
+    InvalidValue,
+    /// An error made with `Error::invalid_length`.
+    InvalidLength,
+    /// An error made with `Error::unknown_variant`.
+    UnknownVariant,
+    /// An error made with `Error::unknown_field`.
+    UnknownField,
+    /// An error made with `Error::missing_field`.
+    MissingField,
+    /// An error made with `Error::duplicate_field`.
+    DuplicateField,
+    /// A call that did not match the expected tokens, including a call made
+    /// after they ran out.
+    ///
+    /// ```
+    /// # use serde_test::{try_de_tokens, ErrorKind};
+    /// #
+    /// let err = try_de_tokens::<u8>(&[]).unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::TokenMismatch);
+    /// ```
+    TokenMismatch,
+}
+
+impl Default for ErrorKind {
+    fn default() -> Self {
+        ErrorKind::Custom
+    }
+}
+
+/// Renders an error message exactly the way serde's default implementations
+/// of the `de::Error` constructors do.
+struct Message(String);
+
+impl Display for Message {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(&self.0)
+    }
+}
+
+impl Debug for Message {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        Debug::fmt(&self.0, formatter)
+    }
+}
+
+impl error::Error for Message {}
+
+impl de::Error for Message {
+    fn custom<T: Display>(msg: T) -> Self {
+        Message(msg.to_string())
+    }
 }
 
 impl ser::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
         Error {
             msg: msg.to_string(),
+            detail: Box::default(),
         }
     }
 }
@@ -19,8 +183,44 @@ impl de::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
         Error {
             msg: msg.to_string(),
+            detail: Box::default(),
         }
     }
+
+    fn invalid_type(unexp: de::Unexpected, exp: &dyn de::Expected) -> Self {
+        let message = Message::invalid_type(unexp, exp);
+        Error::with_kind(message, ErrorKind::InvalidType, Some(unexp.to_string()))
+    }
+
+    fn invalid_value(unexp: de::Unexpected, exp: &dyn de::Expected) -> Self {
+        let message = Message::invalid_value(unexp, exp);
+        Error::with_kind(message, ErrorKind::InvalidValue, Some(unexp.to_string()))
+    }
+
+    fn invalid_length(len: usize, exp: &dyn de::Expected) -> Self {
+        let message = Message::invalid_length(len, exp);
+        Error::with_kind(message, ErrorKind::InvalidLength, None)
+    }
+
+    fn unknown_variant(variant: &str, expected: &'static [&'static str]) -> Self {
+        let message = Message::unknown_variant(variant, expected);
+        Error::with_kind(message, ErrorKind::UnknownVariant, None)
+    }
+
+    fn unknown_field(field: &str, expected: &'static [&'static str]) -> Self {
+        let message = Message::unknown_field(field, expected);
+        Error::with_kind(message, ErrorKind::UnknownField, None)
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        let message = Message::missing_field(field);
+        Error::with_kind(message, ErrorKind::MissingField, None)
+    }
+
+    fn duplicate_field(field: &'static str) -> Self {
+        let message = Message::duplicate_field(field);
+        Error::with_kind(message, ErrorKind::DuplicateField, None)
+    }
 }
 
 impl fmt::Display for Error {
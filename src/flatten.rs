@@ -0,0 +1,57 @@
+use crate::token::Token;
+
+/// Builds the token stream of a struct with `#[serde(flatten)]` fields by
+/// merging the given fragments into a single map.
+///
+/// A struct with a flattened field serializes as a map holding the entries
+/// of the outer fields and of every flattened struct, in declaration order.
+/// Each fragment is either a run of key and value tokens for outer fields,
+/// or the complete stream of a flattened struct or map, whose opening and
+/// closing tokens are dropped. This saves interleaving the merged map by
+/// hand.
+///
+/// ```
+/// # use serde_derive::Serialize;
+/// # use serde_test::{assert_ser_tokens, flatten_map, Token};
+/// #
+/// #[derive(Serialize)]
+/// struct Pagination {
+///     limit: u8,
+/// }
+///
+/// #[derive(Serialize)]
+/// struct Users {
+///     query: &'static str,
+///     #[serde(flatten)]
+///     pagination: Pagination,
+/// }
+///
+/// let pagination = [
+///     Token::Struct { name: "Pagination", len: 1 },
+///     Token::Str("limit"),
+///     Token::U8(10),
+///     Token::StructEnd,
+/// ];
+///
+/// assert_ser_tokens(
+///     &Users {
+///         query: "x",
+///         pagination: Pagination { limit: 10 },
+///     },
+///     &flatten_map(&[&[Token::Str("query"), Token::Str("x")], &pagination]),
+/// );
+/// ```
+#[must_use]
+pub fn flatten_map(fragments: &[&[Token]]) -> Vec<Token> {
+    let mut tokens = vec![Token::Map { len: None }];
+    for fragment in fragments {
+        let entries = match fragment {
+            [Token::Struct { .. }, entries @ .., Token::StructEnd]
+            | [Token::Map { .. }, entries @ .., Token::MapEnd] => entries,
+            entries => entries,
+        };
+        tokens.extend_from_slice(entries);
+    }
+    tokens.push(Token::MapEnd);
+    tokens
+}
@@ -0,0 +1,98 @@
+use std::collections::HashSet;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::{Mutex, MutexGuard, Once, PoisonError};
+
+/// Every string and byte string leaked so far, shared by all threads.
+#[derive(Default)]
+struct Interned {
+    strs: HashSet<&'static str>,
+    bytes: HashSet<&'static [u8]>,
+}
+
+fn interned() -> MutexGuard<'static, Interned> {
+    static INIT: Once = Once::new();
+    static INTERNED: AtomicPtr<Mutex<Interned>> = AtomicPtr::new(ptr::null_mut());
+
+    INIT.call_once(|| {
+        let table = Box::new(Mutex::new(Interned::default()));
+        INTERNED.store(Box::into_raw(table), Ordering::Release);
+    });
+    // SAFETY: the pointer was set from a leaked box by `call_once` above and
+    // is never changed or freed afterwards.
+    let table = unsafe { &*INTERNED.load(Ordering::Acquire) };
+    table.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Returns a `&'static str` equal to `s`, for building tokens from strings
+/// only known at runtime.
+///
+/// The string payloads of [`Token`] are `&'static str` so that tokens stay
+/// `Copy` and can be written as constants. Data-driven tests that read their
+/// expected streams from fixture files can turn each string into a `'static`
+/// one with this function. Every distinct string is leaked once for the rest
+/// of the process, and later calls on any thread return the same allocation,
+/// so interning the same fixture from many tests does not grow memory.
+///
+/// Names of structs, enum variants and fields are `&'static str` for the same
+/// reason and are built the same way, such as when expected streams are
+/// generated from a schema file.
+///
+/// [`Token`]: crate::Token
+///
+/// ```
+/// # use serde_test::{assert_tokens, intern, Token};
+/// #
+/// let fixture = String::from("hello");
+/// assert_tokens(&"hello".to_owned(), &[Token::Str(intern(&fixture))]);
+///
+/// assert!(std::ptr::eq(intern("hello"), intern(&fixture)));
+///
+/// let other = std::thread::spawn(|| intern("hello")).join().unwrap();
+/// assert!(std::ptr::eq(intern("hello"), other));
+/// ```
+///
+/// ```
+/// # use serde_derive::{Deserialize, Serialize};
+/// # use serde_test::{assert_tokens, intern, Token};
+/// #
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Unit;
+///
+/// // Read from a schema at runtime.
+/// let schema_name = String::from("Unit");
+/// assert_tokens(&Unit, &[Token::UnitStruct { name: intern(&schema_name) }]);
+/// ```
+#[must_use]
+pub fn intern(s: &str) -> &'static str {
+    let mut interned = interned();
+    if let Some(&existing) = interned.strs.get(s) {
+        return existing;
+    }
+    let leaked: &'static str = Box::leak(s.to_owned().into_boxed_str());
+    interned.strs.insert(leaked);
+    leaked
+}
+
+/// Returns a `&'static [u8]` equal to `bytes`, for building byte tokens from
+/// buffers only known at runtime.
+///
+/// See [`intern`] for how the bytes are kept.
+///
+/// ```
+/// # use serde_test::{intern_bytes, Token};
+/// #
+/// let fixture = vec![1u8, 2, 3];
+/// let token = Token::ByteBuf(intern_bytes(&fixture));
+/// assert_eq!(token, Token::ByteBuf(&[1, 2, 3]));
+/// ```
+#[must_use]
+pub fn intern_bytes(bytes: &[u8]) -> &'static [u8] {
+    let mut interned = interned();
+    if let Some(&existing) = interned.bytes.get(bytes) {
+        return existing;
+    }
+    let leaked: &'static [u8] = Box::leak(bytes.to_vec().into_boxed_slice());
+    interned.bytes.insert(leaked);
+    leaked
+}
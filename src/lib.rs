@@ -144,15 +144,18 @@
 //! # }
 //! ```
 
-#![doc(html_root_url = "https://docs.rs/serde_test/1.0.177")]
+#![doc(html_root_url = "https://docs.rs/serde_test/2.0.0")]
 // Ignored clippy lints
 #![allow(
     clippy::float_cmp,
     clippy::needless_doctest_main,
-    clippy::needless_lifetimes
+    clippy::needless_lifetimes,
+    clippy::result_large_err,
+    clippy::test_attr_in_doctest
 )]
 // Ignored clippy_pedantic lints
 #![allow(
+    clippy::elidable_lifetime_names,
     clippy::manual_assert,
     clippy::missing_panics_doc,
     clippy::module_name_repetitions,
@@ -160,15 +163,61 @@
 )]
 
 mod assert;
+#[cfg(feature = "bytes-as-base64")]
+mod base64;
+mod capture;
+mod config;
 mod configure;
 mod de;
+mod diff;
 mod error;
+mod flatten;
+mod intern;
+mod memory;
+mod minimize;
+mod mismatch;
+mod ok;
+mod output;
+mod parse;
+mod path;
+pub mod raw;
+mod report;
 mod ser;
 mod token;
+mod tokens;
+mod trace;
+mod untagged;
+mod with_error;
 
 pub use crate::assert::{
-    assert_de_tokens, assert_de_tokens_error, assert_ser_tokens, assert_ser_tokens_error,
-    assert_tokens,
+    assert_de_as, assert_de_error_kind, assert_de_error_unexpected, assert_de_in_place_tokens,
+    assert_de_other_variant, assert_de_owned, assert_de_seed_tokens, assert_de_struct_both_forms,
+    assert_de_tag_positions, assert_de_tokens, assert_de_tokens_borrowed, assert_de_tokens_debug,
+    assert_de_tokens_dual, assert_de_tokens_error, assert_de_tokens_error_at,
+    assert_de_tokens_error_contains, assert_de_tokens_ignore, assert_de_tokens_peak_memory,
+    assert_de_tokens_with, assert_de_tokens_with_config, assert_discriminant, assert_expecting,
+    assert_readable_ne_compact, assert_remote_tokens, assert_roundtrip, assert_roundtrip_redacted,
+    assert_ser_as, assert_ser_bytes_repr, assert_ser_deterministic, assert_ser_max_payload_bytes,
+    assert_ser_max_tokens, assert_ser_tokens, assert_ser_tokens_error,
+    assert_ser_tokens_error_contains, assert_ser_tokens_error_with, assert_ser_tokens_peak_memory,
+    assert_ser_tokens_with_config, assert_ser_trace, assert_tokens, assert_tokens_all,
+    assert_tokens_compact, assert_tokens_concurrent, assert_tokens_readable,
+    assert_tokens_redacted, assert_tokens_with_config, try_assert_de_tokens, try_assert_ser_tokens,
+    try_assert_tokens, try_de_tokens, try_de_tokens_compact, try_de_tokens_readable, ByteRepr,
 };
-pub use crate::configure::{Compact, Configure, Readable};
-pub use crate::token::Token;
+pub use crate::capture::{to_tokens, to_tokens_with_paths};
+pub use crate::config::{Config, DuplicateKeys, Identifier, MapKeys, Predicate, SizeHint};
+pub use crate::configure::{Compact, Configure, HumanReadableProbe, Readable};
+pub use crate::error::ErrorKind;
+pub use crate::flatten::flatten_map;
+pub use crate::intern::{intern, intern_bytes};
+pub use crate::memory::{peak_memory, TrackingAllocator};
+pub use crate::minimize::minimize;
+pub use crate::mismatch::{compare_tokens, Mismatch};
+pub use crate::output::{take_failures, Output};
+pub use crate::parse::parse_tokens;
+pub use crate::report::Report;
+pub use crate::token::{canonicalize, tokens_literal, Token};
+pub use crate::tokens::{TokenFragment, Tokens};
+pub use crate::trace::ser_trace;
+pub use crate::untagged::UntaggedTrace;
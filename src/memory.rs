@@ -0,0 +1,104 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A global allocator that lets `serde_test` measure how much memory is live
+/// while a value is serialized or deserialized.
+///
+/// It forwards to the system allocator. Measurements are only possible once
+/// it has been installed as the `#[global_allocator]` of the test binary.
+///
+/// ```
+/// use serde_test::{peak_memory, TrackingAllocator};
+///
+/// #[global_allocator]
+/// static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+///
+/// fn main() {
+///     let (vec, peak) = peak_memory(|| vec![0u8; 100]);
+///     assert_eq!(vec.len(), 100);
+///     assert!(peak >= 100);
+/// }
+/// ```
+pub struct TrackingAllocator;
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static LIVE: Cell<isize> = Cell::new(0);
+    static PEAK: Cell<isize> = Cell::new(0);
+}
+
+fn record(delta: isize) {
+    let _ = LIVE.try_with(|live| {
+        let now = live.get() + delta;
+        live.set(now);
+        let _ = PEAK.try_with(|peak| {
+            if now > peak.get() {
+                peak.set(now);
+            }
+        });
+    });
+}
+
+// `Layout` sizes never exceed `isize::MAX`.
+#[allow(clippy::cast_possible_wrap)]
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        INSTALLED.store(true, Ordering::Relaxed);
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            record(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        INSTALLED.store(true, Ordering::Relaxed);
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            record(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        record(-(layout.size() as isize));
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            record(new_size as isize - layout.size() as isize);
+        }
+        new_ptr
+    }
+}
+
+/// Runs `f` and returns its result together with the peak number of bytes
+/// that were allocated by the current thread and still live at any point
+/// during the call.
+///
+/// Panics if [`TrackingAllocator`] is not the global allocator.
+// The peak is never below the starting point.
+#[allow(clippy::cast_sign_loss)]
+pub fn peak_memory<F, R>(f: F) -> (R, usize)
+where
+    F: FnOnce() -> R,
+{
+    // Make sure the allocator has seen at least one allocation before
+    // deciding whether it is installed.
+    drop(Box::new(0u8));
+    if !INSTALLED.load(Ordering::Relaxed) {
+        panic!(
+            "measuring memory requires `serde_test::TrackingAllocator` as the #[global_allocator]"
+        );
+    }
+
+    let start = LIVE.with(Cell::get);
+    let outer_peak = PEAK.with(|peak| peak.replace(start));
+    let result = f();
+    let peak = PEAK.with(|peak| peak.replace(outer_peak.max(peak.get())));
+    (result, (peak - start) as usize)
+}
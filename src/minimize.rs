@@ -0,0 +1,107 @@
+use crate::token::Token;
+
+/// Shrinks a token stream while `fails` keeps returning true for it.
+///
+/// Tokens are removed in progressively smaller runs and the remaining scalar
+/// tokens are replaced with the simplest value of the same kind, for as long
+/// as the failure persists. The result is a locally minimal stream that
+/// still reproduces the failure, which is far easier to debug than a large
+/// generated stream.
+///
+/// ```
+/// # use serde_test::{assert_de_tokens_error, minimize, Token};
+/// # use std::panic;
+/// #
+/// # panic::set_hook(Box::new(|_| {}));
+/// let tokens = [
+///     Token::Seq { len: Some(4) },
+///     Token::U8(1),
+///     Token::U8(2),
+///     Token::U8(200),
+///     Token::U8(3),
+///     Token::SeqEnd,
+/// ];
+///
+/// let minimal = minimize(&tokens, |tokens| {
+///     panic::catch_unwind(|| {
+///         assert_de_tokens_error::<Vec<i8>>(
+///             tokens,
+///             "invalid value: integer `200`, expected i8",
+///         );
+///     })
+///     .is_ok()
+/// });
+///
+/// assert_eq!(minimal, [Token::Seq { len: Some(4) }, Token::U8(200)]);
+/// ```
+pub fn minimize<F>(tokens: &[Token], mut fails: F) -> Vec<Token>
+where
+    F: FnMut(&[Token]) -> bool,
+{
+    let mut tokens = tokens.to_vec();
+    loop {
+        let mut changed = false;
+
+        let mut chunk = tokens.len() / 2;
+        while chunk > 0 {
+            let mut start = 0;
+            while start < tokens.len() {
+                let end = (start + chunk).min(tokens.len());
+                let mut candidate = tokens.clone();
+                candidate.drain(start..end);
+                if fails(&candidate) {
+                    tokens = candidate;
+                    changed = true;
+                } else {
+                    start += chunk;
+                }
+            }
+            chunk /= 2;
+        }
+
+        for i in 0..tokens.len() {
+            if let Some(simpler) = simplify(tokens[i]) {
+                let mut candidate = tokens.clone();
+                candidate[i] = simpler;
+                if fails(&candidate) {
+                    tokens = candidate;
+                    changed = true;
+                }
+            }
+        }
+
+        if !changed {
+            return tokens;
+        }
+    }
+}
+
+fn simplify(token: Token) -> Option<Token> {
+    let simpler = match token {
+        Token::Bool(_) => Token::Bool(false),
+        Token::I8(_) => Token::I8(0),
+        Token::I16(_) => Token::I16(0),
+        Token::I32(_) => Token::I32(0),
+        Token::I64(_) => Token::I64(0),
+        Token::U8(_) => Token::U8(0),
+        Token::U16(_) => Token::U16(0),
+        Token::U32(_) => Token::U32(0),
+        Token::U64(_) => Token::U64(0),
+        Token::F32(_) => Token::F32(0.0),
+        Token::F64(_) => Token::F64(0.0),
+        Token::Char(_) => Token::Char('\0'),
+        Token::Str(_) => Token::Str(""),
+        Token::BorrowedStr(_) => Token::BorrowedStr(""),
+        Token::String(_) => Token::String(""),
+        Token::CollectStr(_) => Token::CollectStr(""),
+        Token::Bytes(_) => Token::Bytes(b""),
+        Token::BorrowedBytes(_) => Token::BorrowedBytes(b""),
+        Token::ByteBuf(_) => Token::ByteBuf(b""),
+        _ => return None,
+    };
+    if simpler == token {
+        None
+    } else {
+        Some(simpler)
+    }
+}
@@ -0,0 +1,295 @@
+use crate::error::Error;
+use crate::token::{token_matches, Token};
+use std::fmt::{self, Debug, Display};
+
+/// The details of a failed assertion.
+///
+/// Assertions run with [`Config::mismatch_payload`] or
+/// [`Output::mismatch_payload`] enabled panic with a `Mismatch` as the panic
+/// payload, so harnesses that catch the panic can downcast it and inspect the
+/// failure programmatically.
+///
+/// [`Config::mismatch_payload`]: crate::Config::mismatch_payload
+/// [`Output::mismatch_payload`]: crate::Output::mismatch_payload
+///
+/// ```
+/// # use serde_test::{assert_ser_tokens_with_config, Config, Mismatch, Token};
+/// # use std::panic;
+/// #
+/// # panic::set_hook(Box::new(|_| {}));
+/// let payload = panic::catch_unwind(|| {
+///     let config = Config::new().mismatch_payload(true);
+///     assert_ser_tokens_with_config(
+///         &(1u8, 2u8),
+///         &[Token::Tuple { len: 2 }, Token::U8(1), Token::U8(3)],
+///         config,
+///     );
+/// })
+/// .unwrap_err();
+///
+/// let mismatch = payload.downcast_ref::<Mismatch>().unwrap();
+/// assert_eq!(mismatch.index(), Some(2));
+/// assert_eq!(mismatch.expected(), Some(Token::U8(3)));
+/// assert_eq!(mismatch.actual(), Some("U8(2)"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Mismatch {
+    message: String,
+    index: Option<usize>,
+    expected: Option<Token>,
+    actual: Option<String>,
+    section: Option<String>,
+    path: Option<String>,
+}
+
+/// Compares an `actual` token stream against the `expected` one, returning
+/// the first difference instead of panicking.
+///
+/// Names given as `"*"` in the expected stream match any name, as in the
+/// assertions. This is the comparison for custom harnesses that produce
+/// tokens some other way, such as with [`to_tokens`].
+///
+/// The other matchers, `Token::Any`, `Token::AnyNumber`, `Token::AnyStr`,
+/// `Token::SkipValue`, `Token::UnorderedStart`, `Token::UnorderedEnd` and
+/// `Token::Verify`, depend on the value being serialized and are only
+/// understood by the assertions. Comparing a stream that contains one fails
+/// at its index rather than comparing it token by token.
+///
+/// # Errors
+///
+/// Returns the first difference between the streams, or the first matcher
+/// in `expected`.
+///
+/// [`to_tokens`]: crate::to_tokens
+///
+/// ```
+/// # use serde_test::{compare_tokens, to_tokens, Token};
+/// #
+/// let expected = [Token::Tuple { len: 2 }, Token::U8(1), Token::U8(3), Token::TupleEnd];
+/// let mismatch = compare_tokens(&expected, &to_tokens(&(1u8, 2u8))).unwrap_err();
+/// assert_eq!(mismatch.index(), Some(2));
+/// assert_eq!(mismatch.expected(), Some(Token::U8(3)));
+/// assert_eq!(mismatch.actual(), Some("U8(2)"));
+///
+/// let expected = [Token::Tuple { len: 2 }, Token::U8(1), Token::Any, Token::TupleEnd];
+/// let mismatch = compare_tokens(&expected, &to_tokens(&(1u8, 2u8))).unwrap_err();
+/// assert_eq!(mismatch.index(), Some(2));
+/// ```
+pub fn compare_tokens(expected: &[Token], actual: &[Token]) -> Result<(), Mismatch> {
+    if let Some(index) = expected.iter().position(|&token| is_matcher(token)) {
+        let token = expected[index];
+        return Err(Mismatch {
+            message: format!("{} is only supported by the assertions", token),
+            index: Some(index),
+            expected: Some(token),
+            actual: None,
+            section: None,
+            path: None,
+        });
+    }
+    for (index, (&e, &a)) in expected.iter().zip(actual).enumerate() {
+        if !token_matches(e, a) {
+            return Err(Mismatch {
+                message: format!("expected {} but found {}", e, a),
+                index: Some(index),
+                expected: Some(e),
+                actual: Some(format!("{:?}", a)),
+                section: None,
+                path: None,
+            });
+        }
+    }
+    if let Some(&a) = actual.get(expected.len()) {
+        return Err(Mismatch {
+            message: format!("expected end of tokens but found {}", a),
+            index: Some(expected.len()),
+            expected: None,
+            actual: Some(format!("{:?}", a)),
+            section: None,
+            path: None,
+        });
+    }
+    if expected.len() > actual.len() {
+        return Err(Mismatch::remaining(expected, actual.len()));
+    }
+    Ok(())
+}
+
+/// Whether `token` matches according to the value being serialized, which
+/// a plain token comparison cannot do.
+fn is_matcher(token: Token) -> bool {
+    matches!(
+        token,
+        Token::Any
+            | Token::AnyNumber
+            | Token::AnyStr
+            | Token::SkipValue
+            | Token::UnorderedStart
+            | Token::UnorderedEnd
+            | Token::Verify(_)
+    )
+}
+
+impl Mismatch {
+    pub(crate) fn new(message: String) -> Self {
+        Mismatch {
+            message,
+            index: None,
+            expected: None,
+            actual: None,
+            section: None,
+            path: None,
+        }
+    }
+
+    /// The same failure, attributed to the named section of a [`Tokens`].
+    ///
+    /// [`Tokens`]: crate::Tokens
+    pub(crate) fn in_section(self, name: &str) -> Self {
+        Mismatch {
+            section: Some(name.to_owned()),
+            ..self
+        }
+    }
+
+    /// A failure caused by an error from the serializer or deserializer,
+    /// described as `context: error` or `context at path: error`.
+    pub(crate) fn from_error(context: &str, error: &Error) -> Self {
+        let detail = &error.detail;
+        let message = match &detail.path {
+            Some(path) => format!("{} at {}: {}", context, path, error),
+            None => format!("{}: {}", context, error),
+        };
+        Mismatch {
+            message,
+            index: detail.index,
+            expected: detail.expected,
+            actual: detail.actual.clone(),
+            section: None,
+            path: detail.path.clone(),
+        }
+    }
+
+    /// A failure caused by a deserialized value that differs from the
+    /// expected one, described the way `assert_eq!` would.
+    pub(crate) fn values<A, E>(actual: &A, expected: &E) -> Self
+    where
+        A: ?Sized + Debug,
+        E: ?Sized + Debug,
+    {
+        Mismatch::new(format!(
+            "assertion `left == right` failed\n  left: {:?}\n right: {:?}",
+            actual, expected,
+        ))
+    }
+
+    /// A failure detected at the token at `index` of the expected stream.
+    pub(crate) fn at_index(message: String, index: usize) -> Self {
+        Mismatch {
+            index: Some(index),
+            ..Mismatch::new(message)
+        }
+    }
+
+    /// A failure caused by tokens that were left over at `index`.
+    pub(crate) fn remaining(tokens: &[Token], index: usize) -> Self {
+        let remaining = &tokens[index..];
+        Mismatch {
+            message: format!("{} remaining tokens", remaining.len()),
+            index: Some(index),
+            expected: remaining.first().copied(),
+            actual: None,
+            section: None,
+            path: None,
+        }
+    }
+
+    /// A failure caused by an error that came after `index` tokens were
+    /// consumed rather than after the expected `consumed`.
+    pub(crate) fn error_position(tokens: &[Token], consumed: usize, index: usize) -> Self {
+        Mismatch {
+            message: format!(
+                "expected the error after {} tokens but it came after {}",
+                consumed, index,
+            ),
+            index: Some(index),
+            expected: tokens.get(index).copied(),
+            actual: None,
+            section: None,
+            path: None,
+        }
+    }
+
+    /// The message describing the failure.
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The position in the expected token stream at which the failure
+    /// happened, if it can be attributed to one.
+    #[must_use]
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    /// The token the test expected at `index`, if there was one.
+    #[must_use]
+    pub fn expected(&self) -> Option<Token> {
+        self.expected
+    }
+
+    /// What the `Serialize` or `Deserialize` impl produced or asked for
+    /// instead, written like a token.
+    #[must_use]
+    pub fn actual(&self) -> Option<&str> {
+        self.actual.as_deref()
+    }
+
+    /// The location in the data model of the value that failed, such as
+    /// `servers[3].port`, if it was nested inside a compound value.
+    ///
+    /// ```
+    /// # use serde_test::{assert_ser_tokens_with_config, Config, Mismatch, Token};
+    /// # use std::panic;
+    /// #
+    /// # panic::set_hook(Box::new(|_| {}));
+    /// let payload = panic::catch_unwind(|| {
+    ///     assert_ser_tokens_with_config(
+    ///         &vec![(1u8, 2u8)],
+    ///         &[
+    ///             Token::Seq { len: Some(1) },
+    ///             Token::Tuple { len: 2 },
+    ///             Token::U8(1),
+    ///             Token::U8(3),
+    ///             Token::TupleEnd,
+    ///             Token::SeqEnd,
+    ///         ],
+    ///         Config::new().mismatch_payload(true),
+    ///     );
+    /// })
+    /// .unwrap_err();
+    ///
+    /// let mismatch = payload.downcast_ref::<Mismatch>().unwrap();
+    /// assert_eq!(mismatch.path(), Some("[0][1]"));
+    /// ```
+    #[must_use]
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    /// The name of the section of a [`Tokens`] in which the failure
+    /// happened, if the expected stream was split into sections.
+    ///
+    /// [`Tokens`]: crate::Tokens
+    #[must_use]
+    pub fn section(&self) -> Option<&str> {
+        self.section.as_deref()
+    }
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(&self.message)
+    }
+}
@@ -0,0 +1,341 @@
+use crate::error::Error;
+use crate::ser::Serializer;
+use serde::ser::{self, Serialize};
+
+/// Wraps a [`Serializer`] so that it succeeds with a value of the caller's
+/// choosing instead of `()`.
+///
+/// Generic code written against `S::Ok` — returning a marker from a helper,
+/// or threading the result of one serializer call into the next — cannot be
+/// exercised through the unit `Ok` of the plain serializer. The wrapper checks
+/// calls against the expected tokens exactly as the serializer it wraps does,
+/// and on success hands back whatever `ok` produces.
+///
+/// ```
+/// use serde::ser::{Serialize, SerializeTuple, Serializer as _};
+/// use serde_test::raw::{Serializer, WithOk};
+/// use serde_test::Token;
+///
+/// #[derive(PartialEq, Debug)]
+/// struct Written(usize);
+///
+/// fn write_pair<S>(serializer: S, a: u8, b: u8) -> Result<S::Ok, S::Error>
+/// where
+///     S: serde::Serializer,
+/// {
+///     let mut tuple = serializer.serialize_tuple(2)?;
+///     tuple.serialize_element(&a)?;
+///     tuple.serialize_element(&b)?;
+///     tuple.end()
+/// }
+///
+/// let tokens = [Token::Tuple { len: 2 }, Token::U8(1), Token::U8(2), Token::TupleEnd];
+/// let mut ser = Serializer::new(&tokens);
+/// let written = write_pair(WithOk::new(&mut ser, || Written(2)), 1, 2).unwrap();
+/// assert_eq!(written, Written(2));
+/// assert_eq!(ser.remaining(), 0);
+/// ```
+pub struct WithOk<'s, 'a, F> {
+    ser: &'s mut Serializer<'a>,
+    ok: F,
+}
+
+impl<'s, 'a, F, O> WithOk<'s, 'a, F>
+where
+    F: FnOnce() -> O,
+{
+    /// Wraps `ser`, calling `ok` to produce the value of a successful
+    /// serialization.
+    pub fn new(ser: &'s mut Serializer<'a>, ok: F) -> Self {
+        WithOk { ser, ok }
+    }
+}
+
+/// One of the compound serializers of the wrapped serializer, ending with the
+/// caller's `Ok` value.
+pub struct Compound<C, F> {
+    inner: C,
+    ok: F,
+}
+
+macro_rules! forward {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            fn $method(self, $($arg: $ty),*) -> Result<O, Error> {
+                self.ser.$method($($arg),*)?;
+                Ok((self.ok)())
+            }
+        )*
+    };
+}
+
+macro_rules! forward_compound {
+    ($($method:ident($($arg:ident: $ty:ty),*) -> $compound:ident;)*) => {
+        $(
+            fn $method(self, $($arg: $ty),*) -> Result<Self::$compound, Error> {
+                let inner = self.ser.$method($($arg),*)?;
+                Ok(Compound { inner, ok: self.ok })
+            }
+        )*
+    };
+}
+
+impl<'s, 'a, F, O> ser::Serializer for WithOk<'s, 'a, F>
+where
+    F: FnOnce() -> O,
+{
+    type Ok = O;
+    type Error = Error;
+
+    type SerializeSeq = Compound<<&'s mut Serializer<'a> as ser::Serializer>::SerializeSeq, F>;
+    type SerializeTuple = Compound<<&'s mut Serializer<'a> as ser::Serializer>::SerializeTuple, F>;
+    type SerializeTupleStruct =
+        Compound<<&'s mut Serializer<'a> as ser::Serializer>::SerializeTupleStruct, F>;
+    type SerializeTupleVariant =
+        Compound<<&'s mut Serializer<'a> as ser::Serializer>::SerializeTupleVariant, F>;
+    type SerializeMap = Compound<<&'s mut Serializer<'a> as ser::Serializer>::SerializeMap, F>;
+    type SerializeStruct =
+        Compound<<&'s mut Serializer<'a> as ser::Serializer>::SerializeStruct, F>;
+    type SerializeStructVariant =
+        Compound<<&'s mut Serializer<'a> as ser::Serializer>::SerializeStructVariant, F>;
+
+    forward! {
+        serialize_bool(v: bool);
+        serialize_i8(v: i8);
+        serialize_i16(v: i16);
+        serialize_i32(v: i32);
+        serialize_i64(v: i64);
+        serialize_i128(v: i128);
+        serialize_u8(v: u8);
+        serialize_u16(v: u16);
+        serialize_u32(v: u32);
+        serialize_u64(v: u64);
+        serialize_u128(v: u128);
+        serialize_f32(v: f32);
+        serialize_f64(v: f64);
+        serialize_char(v: char);
+        serialize_str(v: &str);
+        serialize_bytes(v: &[u8]);
+        serialize_unit();
+        serialize_unit_struct(name: &'static str);
+        serialize_unit_variant(name: &'static str, variant_index: u32, variant: &'static str);
+        serialize_none();
+    }
+
+    forward_compound! {
+        serialize_seq(len: Option<usize>) -> SerializeSeq;
+        serialize_tuple(len: usize) -> SerializeTuple;
+        serialize_tuple_struct(name: &'static str, len: usize) -> SerializeTupleStruct;
+        serialize_tuple_variant(
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+            len: usize
+        ) -> SerializeTupleVariant;
+        serialize_map(len: Option<usize>) -> SerializeMap;
+        serialize_struct(name: &'static str, len: usize) -> SerializeStruct;
+        serialize_struct_variant(
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+            len: usize
+        ) -> SerializeStructVariant;
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<O, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.ser.serialize_newtype_struct(name, value)?;
+        Ok((self.ok)())
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<O, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.ser
+            .serialize_newtype_variant(name, variant_index, variant, value)?;
+        Ok((self.ok)())
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<O, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.ser.serialize_some(value)?;
+        Ok((self.ok)())
+    }
+
+    fn is_human_readable(&self) -> bool {
+        ser::Serializer::is_human_readable(&self.ser)
+    }
+}
+
+impl<C, F, O> ser::SerializeSeq for Compound<C, F>
+where
+    C: ser::SerializeSeq<Ok = (), Error = Error>,
+    F: FnOnce() -> O,
+{
+    type Ok = O;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner.serialize_element(value)
+    }
+
+    fn end(self) -> Result<O, Error> {
+        self.inner.end()?;
+        Ok((self.ok)())
+    }
+}
+
+impl<C, F, O> ser::SerializeTuple for Compound<C, F>
+where
+    C: ser::SerializeTuple<Ok = (), Error = Error>,
+    F: FnOnce() -> O,
+{
+    type Ok = O;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner.serialize_element(value)
+    }
+
+    fn end(self) -> Result<O, Error> {
+        self.inner.end()?;
+        Ok((self.ok)())
+    }
+}
+
+impl<C, F, O> ser::SerializeTupleStruct for Compound<C, F>
+where
+    C: ser::SerializeTupleStruct<Ok = (), Error = Error>,
+    F: FnOnce() -> O,
+{
+    type Ok = O;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner.serialize_field(value)
+    }
+
+    fn end(self) -> Result<O, Error> {
+        self.inner.end()?;
+        Ok((self.ok)())
+    }
+}
+
+impl<C, F, O> ser::SerializeTupleVariant for Compound<C, F>
+where
+    C: ser::SerializeTupleVariant<Ok = (), Error = Error>,
+    F: FnOnce() -> O,
+{
+    type Ok = O;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner.serialize_field(value)
+    }
+
+    fn end(self) -> Result<O, Error> {
+        self.inner.end()?;
+        Ok((self.ok)())
+    }
+}
+
+impl<C, F, O> ser::SerializeMap for Compound<C, F>
+where
+    C: ser::SerializeMap<Ok = (), Error = Error>,
+    F: FnOnce() -> O,
+{
+    type Ok = O;
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner.serialize_key(key)
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner.serialize_value(value)
+    }
+
+    fn end(self) -> Result<O, Error> {
+        self.inner.end()?;
+        Ok((self.ok)())
+    }
+}
+
+impl<C, F, O> ser::SerializeStruct for Compound<C, F>
+where
+    C: ser::SerializeStruct<Ok = (), Error = Error>,
+    F: FnOnce() -> O,
+{
+    type Ok = O;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner.serialize_field(key, value)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), Error> {
+        self.inner.skip_field(key)
+    }
+
+    fn end(self) -> Result<O, Error> {
+        self.inner.end()?;
+        Ok((self.ok)())
+    }
+}
+
+impl<C, F, O> ser::SerializeStructVariant for Compound<C, F>
+where
+    C: ser::SerializeStructVariant<Ok = (), Error = Error>,
+    F: FnOnce() -> O,
+{
+    type Ok = O;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner.serialize_field(key, value)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), Error> {
+        self.inner.skip_field(key)
+    }
+
+    fn end(self) -> Result<O, Error> {
+        self.inner.end()?;
+        Ok((self.ok)())
+    }
+}
@@ -0,0 +1,309 @@
+use crate::config::Config;
+use crate::mismatch::Mismatch;
+use crate::token::Token;
+use crate::tokens::Section;
+use std::cell::{Cell, RefCell};
+use std::env;
+use std::fmt::Write;
+use std::panic;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+static COLOR: AtomicBool = AtomicBool::new(false);
+static CONTEXT: AtomicUsize = AtomicUsize::new(0);
+static FULL_STREAMS: AtomicBool = AtomicBool::new(false);
+static MISMATCH_PAYLOAD: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static COLLECT_FAILURES: Cell<bool> = Cell::new(false);
+    static FAILURES: RefCell<Vec<Mismatch>> = RefCell::new(Vec::new());
+    static CATCHING: Cell<bool> = Cell::new(false);
+}
+
+/// Process-wide settings for how every assertion function reports a
+/// failure.
+///
+/// Unlike [`Config`], which is passed to a single assertion, these settings
+/// are installed once, typically at the start of a test suite, and apply to
+/// all assertions on all threads from then on. The exception is
+/// [`collect_failures`], which only applies to the thread that installs it,
+/// so that tests running in parallel keep panicking on their own failures.
+/// Every option is disabled by default, which reports failures as a plain
+/// panic message.
+///
+/// Setting the `SERDE_TEST_FULL_STREAMS` environment variable to anything
+/// other than `0` enables [`full_streams`] without changing any code, which
+/// is handy for rerunning a single failing test.
+///
+/// [`full_streams`]: Output::full_streams
+/// [`collect_failures`]: Output::collect_failures
+///
+/// [`Config`]: crate::Config
+///
+/// ```
+/// # use serde_test::{assert_ser_tokens, take_failures, Output, Token};
+/// #
+/// Output::new().collect_failures(true).install();
+///
+/// assert_ser_tokens(&1u8, &[Token::U16(1)]);
+///
+/// let failures = take_failures();
+/// assert_eq!(failures.len(), 1);
+/// assert_eq!(failures[0].expected(), Some(Token::U16(1)));
+/// ```
+#[derive(Clone, Debug, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Output {
+    verbose: bool,
+    color: bool,
+    context: usize,
+    full_streams: bool,
+    collect_failures: bool,
+    mismatch_payload: bool,
+}
+
+impl Output {
+    /// Creates settings with every option disabled.
+    #[must_use]
+    pub fn new() -> Self {
+        Output::default()
+    }
+
+    /// Returns the settings currently installed.
+    pub fn current() -> Self {
+        Output {
+            verbose: VERBOSE.load(Ordering::Relaxed),
+            color: COLOR.load(Ordering::Relaxed),
+            context: CONTEXT.load(Ordering::Relaxed),
+            full_streams: FULL_STREAMS.load(Ordering::Relaxed)
+                || env::var_os("SERDE_TEST_FULL_STREAMS").map_or(false, |v| v != "0"),
+            collect_failures: COLLECT_FAILURES.with(Cell::get),
+            mismatch_payload: MISMATCH_PAYLOAD.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Installs these settings for every assertion made afterwards, on any
+    /// thread except for [`collect_failures`], which is installed for the
+    /// current thread only.
+    ///
+    /// [`collect_failures`]: Output::collect_failures
+    pub fn install(self) {
+        VERBOSE.store(self.verbose, Ordering::Relaxed);
+        COLOR.store(self.color, Ordering::Relaxed);
+        CONTEXT.store(self.context, Ordering::Relaxed);
+        FULL_STREAMS.store(self.full_streams, Ordering::Relaxed);
+        MISMATCH_PAYLOAD.store(self.mismatch_payload, Ordering::Relaxed);
+        COLLECT_FAILURES.with(|collect| collect.set(self.collect_failures));
+    }
+
+    /// Follows the failure message with the expected token and what the impl
+    /// under test produced or asked for instead.
+    #[must_use]
+    pub fn verbose(mut self, enable: bool) -> Self {
+        self.verbose = enable;
+        self
+    }
+
+    /// Highlights the expected and actual sides of a failure with ANSI
+    /// colors.
+    #[must_use]
+    pub fn color(mut self, enable: bool) -> Self {
+        self.color = enable;
+        self
+    }
+
+    /// Lists up to `context` tokens of the expected stream on either side of
+    /// the token at which an assertion failed.
+    #[must_use]
+    pub fn context(mut self, context: usize) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Lists every token of the expected stream, however long, marking the one
+    /// at which an assertion failed.
+    ///
+    /// This overrides [`context`] and the section of a [`Tokens`], for when
+    /// the cause of a failure lies far from where it was detected.
+    ///
+    /// [`context`]: Output::context
+    /// [`Tokens`]: crate::Tokens
+    #[must_use]
+    pub fn full_streams(mut self, enable: bool) -> Self {
+        self.full_streams = enable;
+        self
+    }
+
+    /// Makes a failing assertion return instead of panicking, after recording
+    /// the failure for [`take_failures`] on the current thread.
+    ///
+    /// Only assertions on the thread that installs the setting are affected.
+    /// Assertions on any other thread still panic.
+    ///
+    /// ```
+    /// # use serde_test::{assert_ser_tokens, take_failures, Output, Token};
+    /// # use std::thread;
+    /// #
+    /// Output::new().collect_failures(true).install();
+    ///
+    /// let other = thread::spawn(|| assert_ser_tokens(&1u8, &[Token::U16(1)]));
+    /// assert!(other.join().is_err());
+    ///
+    /// assert_ser_tokens(&1u8, &[Token::U16(1)]);
+    /// assert_eq!(take_failures().len(), 1);
+    /// ```
+    #[must_use]
+    pub fn collect_failures(mut self, enable: bool) -> Self {
+        self.collect_failures = enable;
+        self
+    }
+
+    /// Makes a failing assertion panic with a [`Mismatch`] as the panic
+    /// payload instead of a formatted message, like
+    /// [`Config::mismatch_payload`] but for every assertion, including the
+    /// plain ones that take no `Config`.
+    ///
+    /// [`Mismatch`]: crate::Mismatch
+    /// [`Config::mismatch_payload`]: crate::Config::mismatch_payload
+    ///
+    /// ```
+    /// # use serde_test::{assert_tokens, Mismatch, Output, Token};
+    /// # use std::panic;
+    /// #
+    /// Output::new().mismatch_payload(true).install();
+    ///
+    /// # panic::set_hook(Box::new(|_| {}));
+    /// let payload = panic::catch_unwind(|| {
+    ///     assert_tokens(&1u8, &[Token::U16(1)]);
+    /// })
+    /// .unwrap_err();
+    ///
+    /// let mismatch = payload.downcast::<Mismatch>().unwrap();
+    /// assert_eq!(mismatch.expected(), Some(Token::U16(1)));
+    /// ```
+    #[must_use]
+    pub fn mismatch_payload(mut self, enable: bool) -> Self {
+        self.mismatch_payload = enable;
+        self
+    }
+
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", color, text)
+        } else {
+            text.to_owned()
+        }
+    }
+
+    fn render(&self, mismatch: &Mismatch, tokens: &[Token], section: Option<&Section>) -> String {
+        let mut message = match section {
+            Some(section) => format!("in section `{}`: {}", section.name, mismatch.message()),
+            None => mismatch.message().to_owned(),
+        };
+        if self.verbose {
+            if let Some(expected) = mismatch.expected() {
+                let expected = expected.to_string();
+                let _ = write!(message, "\nexpected: {}", self.paint("32", &expected));
+            }
+            if let Some(actual) = mismatch.actual() {
+                let actual = format!("Token::{}", actual);
+                let _ = write!(message, "\n  actual: {}", self.paint("31", &actual));
+            }
+        }
+        let window = match (mismatch.index(), section) {
+            (Some(index), _) if self.full_streams => Some((index, 0, tokens.len())),
+            (Some(index), _) if self.context > 0 => {
+                let mut start = index.saturating_sub(self.context);
+                let mut end = tokens.len().min(index + self.context + 1);
+                if let Some(section) = section {
+                    start = start.max(section.range.start);
+                    end = end.min(section.range.end);
+                }
+                Some((index, start, end))
+            }
+            (Some(index), Some(section)) => Some((index, section.range.start, section.range.end)),
+            _ => None,
+        };
+        if let Some((index, start, end)) = window {
+            for (i, token) in tokens.iter().enumerate().take(end).skip(start) {
+                let line = format!("{:>4}: {}", i, token);
+                if i == index {
+                    let _ = write!(message, "\n> {}", self.paint("1", &line));
+                } else {
+                    let _ = write!(message, "\n  {}", line);
+                }
+            }
+        }
+        message
+    }
+}
+
+/// Takes the failures recorded on the current thread while
+/// [`Output::collect_failures`] was enabled.
+#[must_use]
+pub fn take_failures() -> Vec<Mismatch> {
+    FAILURES.with(|failures| failures.borrow_mut().split_off(0))
+}
+
+/// Records failures taken on another thread as if they had happened on the
+/// current one.
+pub(crate) fn record_failures(mismatches: Vec<Mismatch>) {
+    FAILURES.with(|failures| failures.borrow_mut().extend(mismatches));
+}
+
+/// Runs the assertions in `assert` with failures returned instead of
+/// reported, whatever the installed output settings, and returns the first
+/// one.
+pub(crate) fn catch_failure<F>(assert: F) -> Result<(), Mismatch>
+where
+    F: FnOnce(),
+{
+    struct Restore(bool);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CATCHING.with(|catching| catching.set(self.0));
+        }
+    }
+
+    let _restore = Restore(CATCHING.with(|catching| catching.replace(true)));
+    let start = FAILURES.with(|failures| failures.borrow().len());
+    assert();
+    let mut caught = FAILURES.with(|failures| failures.borrow_mut().split_off(start));
+    if caught.is_empty() {
+        Ok(())
+    } else {
+        Err(caught.swap_remove(0))
+    }
+}
+
+/// Reports a failed assertion against the expected `tokens` according to the
+/// installed output settings.
+///
+/// Returns only if failures are being collected.
+#[track_caller]
+pub(crate) fn fail(config: &Config, tokens: &[Token], mismatch: Mismatch) {
+    let output = Output::current();
+    let section = mismatch.index().and_then(|index| {
+        config
+            .sections
+            .iter()
+            .find(|section| section.range.contains(&index))
+    });
+    let mismatch = match section {
+        Some(section) => mismatch.in_section(&section.name),
+        None => mismatch,
+    };
+    if output.collect_failures || CATCHING.with(Cell::get) {
+        FAILURES.with(|failures| failures.borrow_mut().push(mismatch));
+        return;
+    }
+
+    let message = output.render(&mismatch, tokens, section);
+    if config.mismatch_payload || output.mismatch_payload {
+        eprintln!("{}", message);
+        panic::panic_any(mismatch)
+    } else {
+        panic!("{}", message)
+    }
+}
@@ -0,0 +1,554 @@
+use crate::error::Error;
+use crate::intern::{intern, intern_bytes};
+use crate::token::Token;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+use std::fmt::{self, Display, Write};
+use std::str::{self, FromStr};
+use std::vec;
+
+/// Parses a token stream written in a compact text form, so that expected
+/// streams can be kept in fixture files.
+///
+/// Tokens are separated by whitespace and written as their variant name,
+/// followed by their payload or fields in parentheses, in declaration order:
+/// `Struct(Foo, 2)`, `U8(1)`, `UnitVariant(E, A)`. An argument containing
+/// whitespace, a comma or a parenthesis is written in double quotes, where
+/// the usual escapes including `\xNN` for a raw byte are understood. The
+/// length of a `Seq` or `Map` is left out when it is unknown.
+///
+/// Strings and names are turned into `'static` token payloads with
+/// [`intern`].
+///
+/// # Errors
+///
+/// Returns an error naming the offset of the first token that is not valid
+/// notation.
+///
+/// [`intern`]: crate::intern
+///
+/// ```
+/// # use serde_derive::{Deserialize, Serialize};
+/// # use serde_test::{assert_tokens, parse_tokens, Token};
+/// #
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Foo {
+///     a: u8,
+///     b: Vec<String>,
+/// }
+///
+/// let tokens = parse_tokens(
+///     r#"
+///         Struct(Foo, 2)
+///             Str(a) U8(1)
+///             Str(b) Seq(1) String("x, y") SeqEnd
+///         StructEnd
+///     "#,
+/// )
+/// .unwrap();
+///
+/// let foo = Foo {
+///     a: 1,
+///     b: vec!["x, y".to_owned()],
+/// };
+/// assert_tokens(&foo, &tokens);
+///
+/// assert_eq!("Seq".parse::<Token>().unwrap(), Token::Seq { len: None });
+/// ```
+pub fn parse_tokens(input: &str) -> Result<Vec<Token>, Error> {
+    let mut parser = Parser { input, pos: 0 };
+    let mut tokens = Vec::new();
+    loop {
+        parser.skip_whitespace();
+        if parser.at_end() {
+            return Ok(tokens);
+        }
+        tokens.push(parser.token()?);
+    }
+}
+
+/// Parses a single token written as for [`parse_tokens`].
+impl FromStr for Token {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Token, Error> {
+        let mut parser = Parser { input, pos: 0 };
+        parser.skip_whitespace();
+        let token = parser.token()?;
+        parser.skip_whitespace();
+        if !parser.at_end() {
+            return Err(parser.error("expected a single token"));
+        }
+        Ok(token)
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos == self.input.len()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.rest().starts_with(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error<M>(&self, msg: M) -> Error
+    where
+        M: Display,
+    {
+        de::Error::custom(format_args!("{} at offset {}", msg, self.pos))
+    }
+
+    fn token(&mut self) -> Result<Token, Error> {
+        let start = self.pos;
+        let rest = self.rest();
+        let name_len = rest
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(rest.len());
+        let name = &rest[..name_len];
+        if name.is_empty() {
+            return Err(self.error("expected a token name"));
+        }
+        self.pos += name_len;
+        let args = if self.eat('(') {
+            self.args()?
+        } else {
+            Vec::new()
+        };
+        let mut args = Args {
+            args: args.into_iter(),
+        };
+        match build(name, &mut args) {
+            Ok(_) if args.args.len() > 0 => Err(de::Error::custom(format_args!(
+                "too many arguments to {} at offset {}",
+                name, start,
+            ))),
+            Ok(token) => Ok(token),
+            Err(msg) => Err(de::Error::custom(format_args!(
+                "{}: {} at offset {}",
+                name, msg, start,
+            ))),
+        }
+    }
+
+    /// Parses the comma-separated arguments after an opening parenthesis,
+    /// through the closing one.
+    fn args(&mut self) -> Result<Vec<Vec<u8>>, Error> {
+        let mut args = Vec::new();
+        self.skip_whitespace();
+        if self.eat(')') {
+            return Ok(args);
+        }
+        loop {
+            self.skip_whitespace();
+            let arg = if self.eat('"') {
+                self.quoted()?
+            } else {
+                let rest = self.rest();
+                let len = rest.find(|c| c == ',' || c == ')').unwrap_or(rest.len());
+                self.pos += len;
+                rest[..len].trim_end().as_bytes().to_vec()
+            };
+            args.push(arg);
+            self.skip_whitespace();
+            if self.eat(')') {
+                return Ok(args);
+            }
+            if !self.eat(',') {
+                return Err(self.error("expected `,` or `)`"));
+            }
+        }
+    }
+
+    /// Parses the rest of a quoted argument after its opening quote.
+    fn quoted(&mut self) -> Result<Vec<u8>, Error> {
+        let mut arg = Vec::new();
+        let mut chars = self.rest().char_indices();
+        loop {
+            let (i, c) = match chars.next() {
+                Some(next) => next,
+                None => return Err(self.error("unterminated quoted argument")),
+            };
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Ok(arg);
+                }
+                '\\' => {
+                    let escaped = match chars.next() {
+                        Some((_, c)) => c,
+                        None => return Err(self.error("unterminated quoted argument")),
+                    };
+                    match escaped {
+                        'n' => arg.push(b'\n'),
+                        'r' => arg.push(b'\r'),
+                        't' => arg.push(b'\t'),
+                        '0' => arg.push(b'\0'),
+                        '\\' | '"' | '\'' => arg.push(escaped as u8),
+                        'x' => {
+                            let hex: String = chars.by_ref().take(2).map(|(_, c)| c).collect();
+                            match u8::from_str_radix(&hex, 16) {
+                                Ok(byte) if hex.len() == 2 => arg.push(byte),
+                                _ => return Err(self.error("invalid `\\x` escape")),
+                            }
+                        }
+                        _ => return Err(self.error(format_args!("unknown escape `\\{}`", escaped))),
+                    }
+                }
+                _ => {
+                    let mut buf = [0; 4];
+                    arg.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+            }
+        }
+    }
+}
+
+struct Args {
+    args: vec::IntoIter<Vec<u8>>,
+}
+
+impl Args {
+    fn next(&mut self) -> Result<Vec<u8>, String> {
+        self.args
+            .next()
+            .ok_or_else(|| "missing argument".to_owned())
+    }
+
+    fn text(&mut self) -> Result<String, String> {
+        String::from_utf8(self.next()?).map_err(|_| "argument is not valid UTF-8".to_owned())
+    }
+
+    fn str(&mut self) -> Result<&'static str, String> {
+        self.text().map(|text| intern(&text))
+    }
+
+    fn bytes(&mut self) -> Result<&'static [u8], String> {
+        self.next().map(|bytes| intern_bytes(&bytes))
+    }
+
+    fn parse<T>(&mut self) -> Result<T, String>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let text = self.text()?;
+        text.parse()
+            .map_err(|err| format!("invalid argument `{}`: {}", text, err))
+    }
+
+    /// The length of a sequence or map, which is unknown if left out.
+    fn len(&mut self) -> Result<Option<usize>, String> {
+        if self.args.len() == 0 {
+            Ok(None)
+        } else {
+            self.parse().map(Some)
+        }
+    }
+}
+
+fn build(name: &str, args: &mut Args) -> Result<Token, String> {
+    Ok(match name {
+        "Bool" => Token::Bool(args.parse()?),
+        "I8" => Token::I8(args.parse()?),
+        "I16" => Token::I16(args.parse()?),
+        "I32" => Token::I32(args.parse()?),
+        "I64" => Token::I64(args.parse()?),
+        "U8" => Token::U8(args.parse()?),
+        "U16" => Token::U16(args.parse()?),
+        "U32" => Token::U32(args.parse()?),
+        "U64" => Token::U64(args.parse()?),
+        "F32" => Token::F32(args.parse()?),
+        "F64" => Token::F64(args.parse()?),
+        "Char" => Token::Char(args.parse()?),
+        "Str" => Token::Str(args.str()?),
+        "BorrowedStr" => Token::BorrowedStr(args.str()?),
+        "String" => Token::String(args.str()?),
+        "Bytes" => Token::Bytes(args.bytes()?),
+        "BorrowedBytes" => Token::BorrowedBytes(args.bytes()?),
+        "ByteBuf" => Token::ByteBuf(args.bytes()?),
+        "None" => Token::None,
+        "Some" => Token::Some,
+        "Unit" => Token::Unit,
+        "UnitStruct" => Token::UnitStruct { name: args.str()? },
+        "UnitVariant" => Token::UnitVariant {
+            name: args.str()?,
+            variant: args.str()?,
+        },
+        "NewtypeStruct" => Token::NewtypeStruct { name: args.str()? },
+        "NewtypeVariant" => Token::NewtypeVariant {
+            name: args.str()?,
+            variant: args.str()?,
+        },
+        "Seq" => Token::Seq { len: args.len()? },
+        "SeqEnd" => Token::SeqEnd,
+        "Tuple" => Token::Tuple { len: args.parse()? },
+        "TupleEnd" => Token::TupleEnd,
+        "TupleStruct" => Token::TupleStruct {
+            name: args.str()?,
+            len: args.parse()?,
+        },
+        "TupleStructEnd" => Token::TupleStructEnd,
+        "TupleVariant" => Token::TupleVariant {
+            name: args.str()?,
+            variant: args.str()?,
+            len: args.parse()?,
+        },
+        "TupleVariantEnd" => Token::TupleVariantEnd,
+        "Map" => Token::Map { len: args.len()? },
+        "MapEnd" => Token::MapEnd,
+        "Struct" => Token::Struct {
+            name: args.str()?,
+            len: args.parse()?,
+        },
+        "StructEnd" => Token::StructEnd,
+        "StructVariant" => Token::StructVariant {
+            name: args.str()?,
+            variant: args.str()?,
+            len: args.parse()?,
+        },
+        "StructVariantEnd" => Token::StructVariantEnd,
+        "Enum" => Token::Enum { name: args.str()? },
+        "Any" => Token::Any,
+        "AnyNumber" => Token::AnyNumber,
+        "AnyStr" => Token::AnyStr,
+        "SkipValue" => Token::SkipValue,
+        "UnorderedStart" => Token::UnorderedStart,
+        "UnorderedEnd" => Token::UnorderedEnd,
+        "Verify" => Token::Verify(args.str()?),
+        "VariantIndex" => Token::VariantIndex(args.parse()?),
+        "BytesIdentifier" => Token::BytesIdentifier(args.str()?),
+        "AssertHumanReadable" => Token::AssertHumanReadable(args.parse()?),
+        "Error" => Token::Error(args.str()?),
+        "CollectStr" => Token::CollectStr(args.str()?),
+        _ => return Err("unknown token".to_owned()),
+    })
+}
+
+/// Writes a token in the notation read by [`parse_tokens`].
+struct Notation<'a>(&'a Token);
+
+impl<'a> Display for Notation<'a> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        fn len(formatter: &mut fmt::Formatter, name: &str, len: Option<usize>) -> fmt::Result {
+            match len {
+                Some(len) => write!(formatter, "{}({})", name, len),
+                None => formatter.write_str(name),
+            }
+        }
+
+        match *self.0 {
+            Token::Bool(v) => write!(formatter, "Bool({})", v),
+            Token::I8(v) => write!(formatter, "I8({})", v),
+            Token::I16(v) => write!(formatter, "I16({})", v),
+            Token::I32(v) => write!(formatter, "I32({})", v),
+            Token::I64(v) => write!(formatter, "I64({})", v),
+            Token::U8(v) => write!(formatter, "U8({})", v),
+            Token::U16(v) => write!(formatter, "U16({})", v),
+            Token::U32(v) => write!(formatter, "U32({})", v),
+            Token::U64(v) => write!(formatter, "U64({})", v),
+            Token::F32(v) => write!(formatter, "F32({})", v),
+            Token::F64(v) => write!(formatter, "F64({})", v),
+            Token::Char(v) => {
+                let mut buf = [0; 4];
+                write!(
+                    formatter,
+                    "Char({})",
+                    Arg(v.encode_utf8(&mut buf).as_bytes())
+                )
+            }
+            Token::Str(v) => write!(formatter, "Str({})", Arg(v.as_bytes())),
+            Token::BorrowedStr(v) => write!(formatter, "BorrowedStr({})", Arg(v.as_bytes())),
+            Token::String(v) => write!(formatter, "String({})", Arg(v.as_bytes())),
+            Token::Bytes(v) => write!(formatter, "Bytes({})", Arg(v)),
+            Token::BorrowedBytes(v) => write!(formatter, "BorrowedBytes({})", Arg(v)),
+            Token::ByteBuf(v) => write!(formatter, "ByteBuf({})", Arg(v)),
+            Token::None => formatter.write_str("None"),
+            Token::Some => formatter.write_str("Some"),
+            Token::Unit => formatter.write_str("Unit"),
+            Token::UnitStruct { name } => write!(formatter, "UnitStruct({})", Arg(name.as_bytes())),
+            Token::UnitVariant { name, variant } => write!(
+                formatter,
+                "UnitVariant({}, {})",
+                Arg(name.as_bytes()),
+                Arg(variant.as_bytes()),
+            ),
+            Token::NewtypeStruct { name } => {
+                write!(formatter, "NewtypeStruct({})", Arg(name.as_bytes()))
+            }
+            Token::NewtypeVariant { name, variant } => write!(
+                formatter,
+                "NewtypeVariant({}, {})",
+                Arg(name.as_bytes()),
+                Arg(variant.as_bytes()),
+            ),
+            Token::Seq { len: n } => len(formatter, "Seq", n),
+            Token::SeqEnd => formatter.write_str("SeqEnd"),
+            Token::Tuple { len } => write!(formatter, "Tuple({})", len),
+            Token::TupleEnd => formatter.write_str("TupleEnd"),
+            Token::TupleStruct { name, len } => {
+                write!(formatter, "TupleStruct({}, {})", Arg(name.as_bytes()), len)
+            }
+            Token::TupleStructEnd => formatter.write_str("TupleStructEnd"),
+            Token::TupleVariant { name, variant, len } => write!(
+                formatter,
+                "TupleVariant({}, {}, {})",
+                Arg(name.as_bytes()),
+                Arg(variant.as_bytes()),
+                len,
+            ),
+            Token::TupleVariantEnd => formatter.write_str("TupleVariantEnd"),
+            Token::Map { len: n } => len(formatter, "Map", n),
+            Token::MapEnd => formatter.write_str("MapEnd"),
+            Token::Struct { name, len } => {
+                write!(formatter, "Struct({}, {})", Arg(name.as_bytes()), len)
+            }
+            Token::StructEnd => formatter.write_str("StructEnd"),
+            Token::StructVariant { name, variant, len } => write!(
+                formatter,
+                "StructVariant({}, {}, {})",
+                Arg(name.as_bytes()),
+                Arg(variant.as_bytes()),
+                len,
+            ),
+            Token::StructVariantEnd => formatter.write_str("StructVariantEnd"),
+            Token::Enum { name } => write!(formatter, "Enum({})", Arg(name.as_bytes())),
+            Token::Any => formatter.write_str("Any"),
+            Token::AnyNumber => formatter.write_str("AnyNumber"),
+            Token::AnyStr => formatter.write_str("AnyStr"),
+            Token::SkipValue => formatter.write_str("SkipValue"),
+            Token::UnorderedStart => formatter.write_str("UnorderedStart"),
+            Token::UnorderedEnd => formatter.write_str("UnorderedEnd"),
+            Token::Verify(name) => write!(formatter, "Verify({})", Arg(name.as_bytes())),
+            Token::VariantIndex(v) => write!(formatter, "VariantIndex({})", v),
+            Token::BytesIdentifier(v) => {
+                write!(formatter, "BytesIdentifier({})", Arg(v.as_bytes()))
+            }
+            Token::AssertHumanReadable(v) => write!(formatter, "AssertHumanReadable({})", v),
+            Token::Error(v) => write!(formatter, "Error({})", Arg(v.as_bytes())),
+            Token::CollectStr(v) => write!(formatter, "CollectStr({})", Arg(v.as_bytes())),
+        }
+    }
+}
+
+/// An argument, written in quotes if it would not read back as is.
+struct Arg<'a>(&'a [u8]);
+
+impl<'a> Display for Arg<'a> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let plain = match str::from_utf8(self.0) {
+            Ok(s) if !s.is_empty() => {
+                !s.contains(|c: char| c.is_whitespace() || c.is_control() || ",()\"\\".contains(c))
+            }
+            _ => false,
+        };
+        if plain {
+            return formatter.write_str(str::from_utf8(self.0).unwrap());
+        }
+        formatter.write_char('"')?;
+        let mut rest = self.0;
+        while !rest.is_empty() {
+            let (valid, invalid) = match str::from_utf8(rest) {
+                Ok(valid) => (valid, &[][..]),
+                Err(err) => {
+                    let (valid, invalid) = rest.split_at(err.valid_up_to());
+                    (str::from_utf8(valid).unwrap(), invalid)
+                }
+            };
+            for c in valid.chars() {
+                match c {
+                    '"' => formatter.write_str("\\\"")?,
+                    '\\' => formatter.write_str("\\\\")?,
+                    '\n' => formatter.write_str("\\n")?,
+                    '\r' => formatter.write_str("\\r")?,
+                    '\t' => formatter.write_str("\\t")?,
+                    c if c.is_control() => {
+                        let mut buf = [0; 4];
+                        for byte in c.encode_utf8(&mut buf).bytes() {
+                            write!(formatter, "\\x{:02x}", byte)?;
+                        }
+                    }
+                    c => formatter.write_char(c)?,
+                }
+            }
+            if let Some((&byte, tail)) = invalid.split_first() {
+                write!(formatter, "\\x{:02x}", byte)?;
+                rest = tail;
+            } else {
+                rest = &[];
+            }
+        }
+        formatter.write_char('"')
+    }
+}
+
+/// Serializes the token as a string in the notation read by
+/// [`parse_tokens`], so that token streams can be stored with any format.
+///
+/// ```
+/// # use serde_test::{assert_tokens, Token};
+/// #
+/// let stream = vec![Token::Struct { name: "Foo", len: 1 }, Token::Str("a b")];
+/// assert_tokens(
+///     &stream,
+///     &[
+///         Token::Seq { len: Some(2) },
+///         Token::Str("Struct(Foo, 1)"),
+///         Token::Str(r#"Str("a b")"#),
+///         Token::SeqEnd,
+///     ],
+/// );
+/// ```
+impl Serialize for Token {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(&Notation(self))
+    }
+}
+
+impl<'de> Deserialize<'de> for Token {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct TokenVisitor;
+
+        impl<'de> Visitor<'de> for TokenVisitor {
+            type Value = Token;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a token such as `Struct(Foo, 2)`")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Token, E>
+            where
+                E: de::Error,
+            {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(TokenVisitor)
+    }
+}
@@ -0,0 +1,95 @@
+use crate::token::Token;
+use std::fmt::{self, Display};
+
+/// The location of a value within the data model, such as
+/// `servers[3].port`, built up while tokens are matched.
+///
+/// Segments borrow from the expected tokens so that extending the path does
+/// not allocate once its capacity is reserved, which keeps the bookkeeping
+/// out of memory measurements.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Path<'a> {
+    segments: Vec<Segment<'a>>,
+}
+
+#[derive(Clone, Debug)]
+enum Segment<'a> {
+    Field(&'static str),
+    Index(usize),
+    Key(&'a [Token]),
+}
+
+impl<'a> Path<'a> {
+    /// Creates a path with room for `capacity` levels of nesting.
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Path {
+            segments: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub(crate) fn push_field(&mut self, field: &'static str) {
+        self.segments.push(Segment::Field(field));
+    }
+
+    pub(crate) fn push_index(&mut self, index: usize) {
+        self.segments.push(Segment::Index(index));
+    }
+
+    /// Enters the value of the map entry whose key is written as `key`.
+    pub(crate) fn push_key(&mut self, key: &'a [Token]) {
+        self.segments.push(Segment::Key(key));
+    }
+
+    pub(crate) fn pop(&mut self) {
+        self.segments.pop();
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+}
+
+impl<'a> Display for Path<'a> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Field(field) if i == 0 => formatter.write_str(field)?,
+                Segment::Field(field) => write!(formatter, ".{}", field)?,
+                Segment::Index(index) => write!(formatter, "[{}]", index)?,
+                Segment::Key(key) => {
+                    formatter.write_str("[")?;
+                    write_key(formatter, key)?;
+                    formatter.write_str("]")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_key(formatter: &mut fmt::Formatter, key: &[Token]) -> fmt::Result {
+    match key {
+        [Token::Str(s) | Token::BorrowedStr(s) | Token::String(s)] => {
+            write!(formatter, "{:?}", s)
+        }
+        [Token::Char(c)] => write!(formatter, "{:?}", c),
+        [Token::Bool(v)] => write!(formatter, "{}", v),
+        [Token::I8(v)] => write!(formatter, "{}", v),
+        [Token::I16(v)] => write!(formatter, "{}", v),
+        [Token::I32(v)] => write!(formatter, "{}", v),
+        [Token::I64(v)] => write!(formatter, "{}", v),
+        [Token::U8(v)] => write!(formatter, "{}", v),
+        [Token::U16(v)] => write!(formatter, "{}", v),
+        [Token::U32(v)] => write!(formatter, "{}", v),
+        [Token::U64(v)] => write!(formatter, "{}", v),
+        _ => {
+            for (i, token) in key.iter().enumerate() {
+                if i > 0 {
+                    formatter.write_str(", ")?;
+                }
+                write!(formatter, "{:?}", token)?;
+            }
+            Ok(())
+        }
+    }
+}
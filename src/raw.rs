@@ -0,0 +1,26 @@
+//! The building blocks underneath the assertion functions.
+//!
+//! These are meant for crates that layer their own assertions on top of
+//! `serde_test`, for example to check house rules shared by every type in a
+//! codebase. The [`Serializer`] checks each call it receives against the next
+//! expected token, the [`Deserializer`] answers the calls it receives from the
+//! next tokens, and [`token_matches`] is the comparison both of them use.
+//!
+//! ```
+//! use serde::Serialize;
+//! use serde_test::raw::Serializer;
+//! use serde_test::Token;
+//!
+//! let tokens = [Token::Bool(true), Token::Bool(false)];
+//! let mut ser = Serializer::new(&tokens);
+//! true.serialize(&mut ser).unwrap();
+//! assert_eq!(ser.index(), 1);
+//! assert_eq!(ser.remaining(), 1);
+//! ```
+
+pub use crate::de::Deserializer;
+pub use crate::error::Error;
+pub use crate::ok::{Compound, WithOk};
+pub use crate::ser::Serializer;
+pub use crate::token::{name_matches, token_matches};
+pub use crate::with_error::WithError;
@@ -0,0 +1,82 @@
+/// Statistics gathered while making an assertion that passed.
+///
+/// Returned by the `*_with_config` assertion functions so that tests can
+/// make assertions about the shape of the token stream, such as an
+/// optimization keeping the number of tokens under some bound.
+///
+/// ```
+/// # use serde_test::{assert_ser_tokens_with_config, Config, Token};
+/// #
+/// let report = assert_ser_tokens_with_config(
+///     &vec![vec![1u8]],
+///     &[
+///         Token::Seq { len: Some(1) },
+///         Token::Seq { len: Some(1) },
+///         Token::U8(1),
+///         Token::SeqEnd,
+///         Token::SeqEnd,
+///     ],
+///     Config::new(),
+/// );
+/// assert_eq!(report.tokens(), 5);
+/// assert_eq!(report.max_depth(), 2);
+/// assert_eq!(report.skipped(), 0);
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    pub(crate) tokens: usize,
+    pub(crate) max_depth: usize,
+    pub(crate) skipped: usize,
+    pub(crate) bytes: BytesVisits,
+}
+
+/// How many byte tokens the `Deserialize` impl accepted through each of the
+/// `Visitor` methods for bytes.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub(crate) struct BytesVisits {
+    pub(crate) borrowed: usize,
+    pub(crate) transient: usize,
+    pub(crate) owned: usize,
+}
+
+impl Report {
+    /// The number of tokens that were matched or consumed.
+    #[must_use]
+    pub fn tokens(&self) -> usize {
+        self.tokens
+    }
+
+    /// The deepest level of nested compound values.
+    #[must_use]
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// The number of struct fields the `Serialize` impl skipped, or the
+    /// number of values the `Deserialize` impl ignored.
+    #[must_use]
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// The number of byte tokens the `Deserialize` impl accepted through
+    /// `visit_borrowed_bytes`.
+    #[must_use]
+    pub fn borrowed_bytes(&self) -> usize {
+        self.bytes.borrowed
+    }
+
+    /// The number of byte tokens the `Deserialize` impl accepted through
+    /// `visit_bytes`.
+    #[must_use]
+    pub fn transient_bytes(&self) -> usize {
+        self.bytes.transient
+    }
+
+    /// The number of byte tokens the `Deserialize` impl accepted through
+    /// `visit_byte_buf`.
+    #[must_use]
+    pub fn owned_bytes(&self) -> usize {
+        self.bytes.owned
+    }
+}
@@ -1,33 +1,530 @@
+use crate::config::{Config, DuplicateKeys, MapKeys};
+use crate::configure::probe_readable;
+use crate::diff::{hexdump, string_diff, LONG_STRING};
 use crate::error::Error;
-use crate::token::Token;
+use crate::intern::{intern, intern_bytes};
+use crate::path::Path;
+use crate::report::{BytesVisits, Report};
+use crate::token::{closes_compound, name_matches, opens_compound, value_len, Token};
 use serde::ser::{self, Serialize};
+use std::fmt::Display;
 
 /// A `Serializer` that ensures that a value serializes to a given list of
 /// tokens.
 #[derive(Debug)]
 pub struct Serializer<'a> {
     tokens: &'a [Token],
+    len: usize,
+    config: Config,
+    calls: usize,
+    depth: usize,
+    max_depth: usize,
+    skipped: usize,
+    nesting: usize,
+    keys: Vec<Vec<&'a [Token]>>,
+    last_key: &'a [Token],
+    indices: Vec<usize>,
+    path: Path<'a>,
+    unordered: Option<Unordered<'a>>,
+    lens: Vec<LenHint>,
+}
+
+/// The length a compound value was started with, against which the number of
+/// elements or entries actually serialized is checked.
+#[derive(Debug)]
+struct LenHint {
+    method: &'static str,
+    declared: Option<usize>,
+    written: usize,
+}
+
+/// The entries of an unordered group that no serialized element or map entry
+/// has matched yet.
+#[derive(Debug)]
+struct Unordered<'a> {
+    entries: Vec<&'a [Token]>,
+    /// The entries whose key matched the most recent map key.
+    keyed: Vec<usize>,
 }
 
 impl<'a> Serializer<'a> {
     /// Creates the serializer.
+    #[must_use]
     pub fn new(tokens: &'a [Token]) -> Self {
-        Serializer { tokens }
+        Serializer::with_config(tokens, Config::new())
+    }
+
+    /// Creates the serializer with the options in `config`.
+    #[must_use]
+    pub fn with_config(tokens: &'a [Token], config: Config) -> Self {
+        Serializer {
+            tokens,
+            len: tokens.len(),
+            config,
+            calls: 0,
+            depth: 0,
+            max_depth: 0,
+            skipped: 0,
+            nesting: 0,
+            keys: Vec::new(),
+            last_key: &[],
+            indices: Vec::with_capacity(tokens.len()),
+            path: Path::with_capacity(tokens.len()),
+            unordered: None,
+            lens: Vec::new(),
+        }
     }
 
     /// Pulls the next token off of the serializer, ignoring it.
     fn next_token(&mut self) -> Option<Token> {
+        self.calls += 1;
+        if self.config.check_calls(self.calls, self.index()) {
+            self.tokens = &[];
+            return None;
+        }
         if let Some((&first, rest)) = self.tokens.split_first() {
             self.tokens = rest;
+            self.track_depth(first);
             Some(first)
         } else {
             None
         }
     }
 
+    /// The number of expected tokens not yet matched.
+    #[must_use]
     pub fn remaining(&self) -> usize {
         self.tokens.len()
     }
+
+    /// The position of the next token in the expected stream.
+    #[must_use]
+    pub fn index(&self) -> usize {
+        self.len - self.tokens.len()
+    }
+
+    /// The deepest level of nested compound values matched so far.
+    #[must_use]
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Statistics about the tokens matched so far.
+    #[must_use]
+    pub fn report(&self) -> Report {
+        Report {
+            tokens: self.index(),
+            max_depth: self.max_depth,
+            skipped: self.skipped,
+            bytes: BytesVisits::default(),
+        }
+    }
+
+    fn track_depth(&mut self, token: Token) {
+        if opens_compound(token) {
+            self.depth += 1;
+            self.max_depth = self.max_depth.max(self.depth);
+            if self.config.check_depth(self.depth, self.index() - 1) {
+                // Nothing deeper is matched once the failure is reported.
+                self.tokens = &[];
+            }
+        } else if closes_compound(token) {
+            self.depth = self.depth.saturating_sub(1);
+        }
+    }
+
+    /// An error for data serialized against a borrowed token that has the
+    /// same contents but lives somewhere else, meaning the value holds a copy.
+    fn copied(&mut self, actual: String) -> Error {
+        let expected = self.next_token();
+        Error::mismatch(
+            format!(
+                "serialized {} is a copy of the data lent by {}, not a borrow of it",
+                actual,
+                expected.unwrap(),
+            ),
+            self.index() - 1,
+            expected,
+            actual,
+        )
+    }
+
+    /// Starts tracking the keys of a map or struct, if duplicates are checked.
+    fn open_keys(&mut self) {
+        if self.config.duplicate_keys != DuplicateKeys::Allow {
+            self.keys.push(Vec::new());
+        }
+    }
+
+    fn close_keys(&mut self) {
+        if self.config.duplicate_keys != DuplicateKeys::Allow {
+            self.keys.pop();
+        }
+    }
+
+    /// Records the key whose tokens were matched since `before` for the path
+    /// of its value, flagging it if the innermost map or struct already has
+    /// the same key.
+    fn check_key(&mut self, before: &'a [Token]) -> Result<(), Error> {
+        let key = &before[..before.len() - self.tokens.len()];
+        self.last_key = key;
+        let keys = match self.keys.last_mut() {
+            Some(keys) => keys,
+            None => return Ok(()),
+        };
+        if keys.contains(&key) {
+            let rendered: Vec<String> = key.iter().map(ToString::to_string).collect();
+            let msg = format!(
+                "duplicate key [{}] serialized in the same map or struct",
+                rendered.join(", "),
+            );
+            match self.config.duplicate_keys {
+                DuplicateKeys::Deny => return Err(ser::Error::custom(msg)),
+                DuplicateKeys::Warn => eprintln!("warning: {}", msg),
+                DuplicateKeys::Allow => {}
+            }
+        }
+        keys.push(key);
+        Ok(())
+    }
+
+    /// Fails if the most recent map key is not of a kind allowed by
+    /// `map_keys`.
+    fn check_map_key(&self) -> Result<(), Error> {
+        let allowed = match (self.config.map_keys, self.last_key) {
+            (MapKeys::Any, _)
+            | (MapKeys::Strings, [Token::Str(_) | Token::BorrowedStr(_) | Token::String(_)]) => {
+                true
+            }
+            (MapKeys::Only(allow), [key]) => allow(key),
+            _ => false,
+        };
+        if allowed {
+            return Ok(());
+        }
+        let rendered: Vec<String> = self.last_key.iter().map(ToString::to_string).collect();
+        Err(ser::Error::custom(format_args!(
+            "map key [{}] is not of an allowed kind",
+            rendered.join(", "),
+        )))
+    }
+
+    /// Starts counting the elements or entries of a compound value, if
+    /// length hints are checked.
+    fn open_len(&mut self, method: &'static str, declared: Option<usize>) {
+        if self.config.check_len_hints {
+            self.lens.push(LenHint {
+                method,
+                declared,
+                written: 0,
+            });
+        }
+    }
+
+    fn count_len(&mut self) {
+        if let Some(hint) = self.lens.last_mut() {
+            hint.written += 1;
+        }
+    }
+
+    /// Fails if the compound value being ended was started with a length
+    /// other than the number of elements or entries serialized.
+    fn close_len(&mut self) -> Result<(), Error> {
+        if !self.config.check_len_hints {
+            return Ok(());
+        }
+        match self.lens.pop() {
+            Some(LenHint {
+                method,
+                declared: Some(declared),
+                written,
+            }) if declared != written => Err(ser::Error::custom(format_args!(
+                "{} was given len {} but {} were serialized",
+                method, declared, written,
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    fn open_elements(&mut self) {
+        self.indices.push(0);
+    }
+
+    fn close_elements(&mut self) {
+        self.indices.pop();
+    }
+
+    /// Extends the path with the index of the next element.
+    fn enter_element(&mut self) {
+        let index = match self.indices.last_mut() {
+            Some(next) => {
+                *next += 1;
+                *next - 1
+            }
+            None => 0,
+        };
+        self.path.push_index(index);
+    }
+
+    /// Takes the entries of an unordered group directly following the header
+    /// of a sequence or map. Each entry is `values` values long: one for an
+    /// element, two for a key and its value.
+    fn open_unordered(&mut self, values: usize) -> Result<(), Error> {
+        if self.tokens.first() != Some(&Token::UnorderedStart) {
+            return Ok(());
+        }
+        self.next_token();
+        let mut entries = Vec::new();
+        loop {
+            match self.tokens.first() {
+                Some(Token::UnorderedEnd) => {
+                    self.next_token();
+                    break;
+                }
+                Some(_) => {
+                    let mut len = 0;
+                    for _ in 0..values {
+                        len += value_len(&self.tokens[len..]);
+                    }
+                    entries.push(&self.tokens[..len]);
+                    self.tokens = &self.tokens[len..];
+                }
+                None => {
+                    return Err(Error::token(
+                        "Token::UnorderedStart is missing its Token::UnorderedEnd",
+                    ));
+                }
+            }
+        }
+        self.unordered = Some(Unordered {
+            entries,
+            keyed: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Fails if the unordered group of the sequence or map being ended has
+    /// entries that nothing was serialized for.
+    fn close_unordered(&mut self) -> Result<(), Error> {
+        let unordered = match self.unordered.take() {
+            Some(unordered) => unordered,
+            None => return Ok(()),
+        };
+        match unordered.entries.first() {
+            None => Ok(()),
+            Some(first) => {
+                let rendered: Vec<String> = first.iter().map(ToString::to_string).collect();
+                Err(Error::token(format_args!(
+                    "{} entries of the unordered group were not serialized, including [{}]",
+                    unordered.entries.len(),
+                    rendered.join(", "),
+                )))
+            }
+        }
+    }
+
+    /// Whether `value` serializes to exactly the tokens of `entry`.
+    fn matches_entry<T>(&self, entry: &[Token], value: &T) -> bool
+    where
+        T: ?Sized + Serialize,
+    {
+        let mut ser = Serializer::with_config(entry, self.config.clone());
+        value.serialize(&mut ser).is_ok() && ser.remaining() == 0
+    }
+
+    /// Matches an element against the remaining entries of the unordered
+    /// group, in any order.
+    fn serialize_unordered_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let unordered = self.unordered.as_ref().unwrap();
+        let found = unordered
+            .entries
+            .iter()
+            .position(|entry| self.matches_entry(entry, value));
+        let unordered = self.unordered.as_mut().unwrap();
+        match found {
+            Some(i) => {
+                unordered.entries.remove(i);
+                Ok(())
+            }
+            None => Err(Error::token(format_args!(
+                "element matched none of the {} remaining entries of the unordered group",
+                unordered.entries.len(),
+            ))),
+        }
+    }
+
+    /// Notes which remaining entries of the unordered group have `key` as
+    /// their key.
+    fn serialize_unordered_key<T>(&mut self, key: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let unordered = self.unordered.as_ref().unwrap();
+        let keyed: Vec<usize> = (0..unordered.entries.len())
+            .filter(|&i| {
+                let entry = unordered.entries[i];
+                self.matches_entry(&entry[..value_len(entry)], key)
+            })
+            .collect();
+        let unordered = self.unordered.as_mut().unwrap();
+        if keyed.is_empty() {
+            return Err(Error::token(format_args!(
+                "map key matched none of the {} remaining entries of the unordered group",
+                unordered.entries.len(),
+            )));
+        }
+        unordered.keyed = keyed;
+        Ok(())
+    }
+
+    /// Matches a map value against the entries whose key matched the key
+    /// serialized before it.
+    fn serialize_unordered_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let unordered = self.unordered.as_ref().unwrap();
+        let found = unordered.keyed.iter().copied().find(|&i| {
+            let entry = unordered.entries[i];
+            self.matches_entry(&entry[value_len(entry)..], value)
+        });
+        let unordered = self.unordered.as_mut().unwrap();
+        let keyed = unordered.keyed.len();
+        unordered.keyed.clear();
+        match found {
+            Some(i) => {
+                unordered.entries.remove(i);
+                Ok(())
+            }
+            None => Err(Error::token(format_args!(
+                "map value matched none of the {} entries of the unordered group with its key",
+                keyed,
+            ))),
+        }
+    }
+
+    /// Serializes `value` at the path the caller has just extended, then
+    /// leaves that part of the path again.
+    fn serialize_in<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let result = self
+            .serialize_nested(value)
+            .map_err(|err| err.at(&self.path));
+        self.path.pop();
+        result
+    }
+
+    /// Serializes a value nested inside the one being serialized, failing if
+    /// values nest deeper than `detect_cycles` allows.
+    fn serialize_nested<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if let Some(max_nesting) = self.config.detect_cycles {
+            if self.nesting >= max_nesting {
+                return Err(ser::Error::custom(format_args!(
+                    "possible cycle while serializing: values nested more than {} deep",
+                    max_nesting,
+                )));
+            }
+        }
+        if self.tokens.first() == Some(&Token::SkipValue) {
+            self.next_token();
+            return crate::capture::skip(value);
+        }
+        self.nesting += 1;
+        let result = value.serialize(&mut *self);
+        self.nesting -= 1;
+        result
+    }
+
+    /// Fails on a non-finite float if those are rejected.
+    fn check_finite<F>(&self, finite: bool, v: F) -> Result<(), Error>
+    where
+        F: Display,
+    {
+        if finite || !self.config.reject_non_finite {
+            return Ok(());
+        }
+        Err(ser::Error::custom(format_args!(
+            "non-finite float {} cannot be represented",
+            v,
+        )))
+    }
+
+    /// Fails on a type, variant or field name outside of the set given to
+    /// `known_names`.
+    fn check_names(&self, names: &[&'static str]) -> Result<(), Error> {
+        if let Some(known) = self.config.known_names {
+            for name in names {
+                if !known.contains(name) {
+                    return Err(ser::Error::custom(format_args!(
+                        "name `{}` is not one of the known names",
+                        name,
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Consumes a `Token::Any`, or the narrower `wildcard` for the kind of
+    /// scalar being serialized, standing in for that scalar.
+    fn next_is_any(&mut self, wildcard: Token) -> bool {
+        match self.tokens.first() {
+            Some(&token) if token == Token::Any || token == wildcard => {
+                self.next_token();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Like `next_is_any`, but also consumes a `Token::Verify` whose predicate
+    /// accepts the token that `actual` builds for the scalar, failing if the
+    /// predicate rejects it or was never registered.
+    fn next_is_wildcard<F>(&mut self, wildcard: Token, actual: F) -> Result<bool, Error>
+    where
+        F: FnOnce() -> Token,
+    {
+        if let Some(&Token::Verify(name)) = self.tokens.first() {
+            let actual = actual();
+            let message = match self.config.predicate(name) {
+                Some(verify) if verify(&actual) => None,
+                Some(_) => Some(format!(
+                    "predicate `{}` rejected the serialized {}",
+                    name, actual,
+                )),
+                None => Some(format!(
+                    "no predicate named `{}` was registered with Config::verify",
+                    name,
+                )),
+            };
+            if let Some(message) = message {
+                return Err(Error::mismatch(
+                    message,
+                    self.index(),
+                    Some(Token::Verify(name)),
+                    format!("{:?}", actual),
+                ));
+            }
+            self.next_token();
+            return Ok(true);
+        }
+        Ok(self.next_is_any(wildcard))
+    }
+
+    fn next_is_enum(&self, name: &str) -> bool {
+        match self.tokens.first() {
+            Some(&Token::Enum { name: expected }) => name_matches(expected, name),
+            _ => false,
+        }
+    }
 }
 
 macro_rules! assert_next_token {
@@ -43,7 +540,6 @@ macro_rules! assert_next_token {
         );
     }};
     ($ser:expr, $actual:ident { $($k:ident),* }) => {{
-        let compare = ($($k,)*);
         let field_format = || {
             use std::fmt::Write;
             let mut buffer = String::new();
@@ -55,23 +551,57 @@ macro_rules! assert_next_token {
         assert_next_token!(
             $ser,
             format_args!(concat!(stringify!($actual), " {{ {}}}"), field_format()),
-            Token::$actual { $($k),* },
-            ($($k,)*) == compare
+            expected @ Token::$actual { .. },
+            $ser.config.token_matches(expected, Token::$actual { $($k),* })
         );
     }};
     ($ser:expr, $actual:expr, $pat:pat, $guard:expr) => {
         match $ser.next_token() {
             Some($pat) if $guard => {}
-            Some(expected) => return Err(ser::Error::custom(
-                format!("expected Token::{} but serialized as {}", expected, $actual)
+            Some(expected) => return Err(Error::mismatch(
+                format!("expected {} but serialized as {}", expected, $actual),
+                $ser.index() - 1,
+                Some(expected),
+                $actual.to_string(),
             )),
-            None => return Err(ser::Error::custom(
-                format!("expected end of tokens, but {} was serialized", $actual)
+            None => return Err(Error::mismatch(
+                format!("expected end of tokens, but {} was serialized", $actual),
+                $ser.index(),
+                None,
+                $actual.to_string(),
             )),
         }
     };
 }
 
+impl<'a> Serializer<'a> {
+    /// Matches the variant of an enum written in the `Token::Enum` form,
+    /// either by name or by index.
+    fn serialize_variant_identifier(
+        &mut self,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        match self.tokens.first() {
+            Some(&Token::VariantIndex(_)) => assert_next_token!(self, VariantIndex(variant_index)),
+            Some(&Token::BytesIdentifier(_)) => assert_next_token!(self, BytesIdentifier(variant)),
+            _ => assert_next_token!(self, Str(variant)),
+        }
+        Ok(())
+    }
+
+    /// Matches the key of a struct field, written either as a string or as a
+    /// `Token::BytesIdentifier`.
+    fn serialize_field_key(&mut self, key: &'static str) -> Result<(), Error> {
+        if let Some(&Token::BytesIdentifier(_)) = self.tokens.first() {
+            assert_next_token!(self, BytesIdentifier(key));
+            Ok(())
+        } else {
+            key.serialize(self)
+        }
+    }
+}
+
 impl<'s, 'a> ser::Serializer for &'s mut Serializer<'a> {
     type Ok = ();
     type Error = Error;
@@ -85,66 +615,180 @@ impl<'s, 'a> ser::Serializer for &'s mut Serializer<'a> {
     type SerializeStructVariant = Variant<'s, 'a>;
 
     fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        if self.next_is_wildcard(Token::Any, || Token::Bool(v))? {
+            return Ok(());
+        }
         assert_next_token!(self, Bool(v));
         Ok(())
     }
 
     fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        if self.next_is_wildcard(Token::AnyNumber, || Token::I8(v))? {
+            return Ok(());
+        }
         assert_next_token!(self, I8(v));
         Ok(())
     }
 
     fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        if self.next_is_wildcard(Token::AnyNumber, || Token::I16(v))? {
+            return Ok(());
+        }
         assert_next_token!(self, I16(v));
         Ok(())
     }
 
     fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        if self.next_is_wildcard(Token::AnyNumber, || Token::I32(v))? {
+            return Ok(());
+        }
         assert_next_token!(self, I32(v));
         Ok(())
     }
 
     fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        if self.next_is_wildcard(Token::AnyNumber, || Token::I64(v))? {
+            return Ok(());
+        }
         assert_next_token!(self, I64(v));
         Ok(())
     }
 
+    fn serialize_i128(self, v: i128) -> Result<(), Error> {
+        if self.next_is_any(Token::AnyNumber) {
+            return Ok(());
+        }
+        if self.config.int128_as_strings {
+            self.serialize_str(&v.to_string())
+        } else {
+            Err(ser::Error::custom("i128 is not supported"))
+        }
+    }
+
     fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        if self.next_is_wildcard(Token::AnyNumber, || Token::U8(v))? {
+            return Ok(());
+        }
         assert_next_token!(self, U8(v));
         Ok(())
     }
 
     fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        if self.next_is_wildcard(Token::AnyNumber, || Token::U16(v))? {
+            return Ok(());
+        }
         assert_next_token!(self, U16(v));
         Ok(())
     }
 
     fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        if self.next_is_wildcard(Token::AnyNumber, || Token::U32(v))? {
+            return Ok(());
+        }
         assert_next_token!(self, U32(v));
         Ok(())
     }
 
     fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        if self.next_is_wildcard(Token::AnyNumber, || Token::U64(v))? {
+            return Ok(());
+        }
         assert_next_token!(self, U64(v));
         Ok(())
     }
 
+    fn serialize_u128(self, v: u128) -> Result<(), Error> {
+        if self.next_is_any(Token::AnyNumber) {
+            return Ok(());
+        }
+        if self.config.int128_as_strings {
+            self.serialize_str(&v.to_string())
+        } else {
+            Err(ser::Error::custom("u128 is not supported"))
+        }
+    }
+
     fn serialize_f32(self, v: f32) -> Result<(), Error> {
-        assert_next_token!(self, F32(v));
+        if self.next_is_wildcard(Token::AnyNumber, || Token::F32(v))? {
+            return Ok(());
+        }
+        self.check_finite(v.is_finite(), v)?;
+        assert_next_token!(
+            self,
+            format_args!("F32({:?})", v),
+            Token::F32(expected),
+            self.config.f32_matches(expected, v)
+        );
         Ok(())
     }
 
     fn serialize_f64(self, v: f64) -> Result<(), Error> {
-        assert_next_token!(self, F64(v));
+        if self.next_is_wildcard(Token::AnyNumber, || Token::F64(v))? {
+            return Ok(());
+        }
+        self.check_finite(v.is_finite(), v)?;
+        assert_next_token!(
+            self,
+            format_args!("F64({:?})", v),
+            Token::F64(expected),
+            self.config.f64_matches(expected, v)
+        );
         Ok(())
     }
 
     fn serialize_char(self, v: char) -> Result<(), Error> {
+        if self.next_is_wildcard(Token::Any, || Token::Char(v))? {
+            return Ok(());
+        }
         assert_next_token!(self, Char(v));
         Ok(())
     }
 
     fn serialize_str(self, v: &str) -> Result<(), Error> {
+        if self.next_is_wildcard(Token::AnyStr, || Token::Str(intern(v)))? {
+            return Ok(());
+        }
+        #[cfg(feature = "bytes-as-base64")]
+        {
+            if self.config.bytes_as_base64 {
+                match self.tokens.first() {
+                    Some(&(Token::Bytes(b) | Token::BorrowedBytes(b) | Token::ByteBuf(b)))
+                        if crate::base64::encode(b) == v =>
+                    {
+                        self.next_token();
+                        return Ok(());
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if let Some(&expected) = self.tokens.first() {
+            if let Token::Str(s) | Token::BorrowedStr(s) | Token::String(s) = expected {
+                if s != v && s.chars().count().max(v.chars().count()) >= LONG_STRING {
+                    let kind = match expected {
+                        Token::BorrowedStr(_) => "BorrowedStr",
+                        Token::String(_) => "String",
+                        _ => "Str",
+                    };
+                    self.next_token();
+                    return Err(Error::mismatch(
+                        format!(
+                            "serialized string differs from the expected Token::{}\n{}",
+                            kind,
+                            string_diff(s, v),
+                        ),
+                        self.index() - 1,
+                        Some(expected),
+                        format!("{}({:?})", kind, v),
+                    ));
+                }
+            }
+        }
+        if let Some(&Token::BorrowedStr(s)) = self.tokens.first() {
+            if self.config.check_borrowed && s == v && s.as_ptr() != v.as_ptr() {
+                return Err(self.copied(format!("Str({:?})", v)));
+            }
+        }
         match self.tokens.first() {
             Some(&Token::BorrowedStr(_)) => assert_next_token!(self, BorrowedStr(v)),
             Some(&Token::String(_)) => assert_next_token!(self, String(v)),
@@ -154,6 +798,36 @@ impl<'s, 'a> ser::Serializer for &'s mut Serializer<'a> {
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<(), Self::Error> {
+        if self.next_is_wildcard(Token::Any, || Token::Bytes(intern_bytes(v)))? {
+            return Ok(());
+        }
+        if let Some(&expected) = self.tokens.first() {
+            if let Token::Bytes(b) | Token::BorrowedBytes(b) | Token::ByteBuf(b) = expected {
+                if b != v {
+                    let kind = match expected {
+                        Token::BorrowedBytes(_) => "BorrowedBytes",
+                        Token::ByteBuf(_) => "ByteBuf",
+                        _ => "Bytes",
+                    };
+                    self.next_token();
+                    return Err(Error::mismatch(
+                        format!(
+                            "serialized bytes differ from the expected Token::{}\n{}",
+                            kind,
+                            hexdump(b, v),
+                        ),
+                        self.index() - 1,
+                        Some(expected),
+                        format!("{}({:?})", kind, v),
+                    ));
+                }
+            }
+        }
+        if let Some(&Token::BorrowedBytes(b)) = self.tokens.first() {
+            if self.config.check_borrowed && b == v && b.as_ptr() != v.as_ptr() {
+                return Err(self.copied(format!("Bytes({:?})", v)));
+            }
+        }
         match self.tokens.first() {
             Some(&Token::BorrowedBytes(_)) => assert_next_token!(self, BorrowedBytes(v)),
             Some(&Token::ByteBuf(_)) => assert_next_token!(self, ByteBuf(v)),
@@ -163,11 +837,22 @@ impl<'s, 'a> ser::Serializer for &'s mut Serializer<'a> {
     }
 
     fn serialize_unit(self) -> Result<(), Error> {
+        if self.next_is_wildcard(Token::Any, || Token::Unit)? {
+            return Ok(());
+        }
         assert_next_token!(self, Unit);
         Ok(())
     }
 
     fn serialize_unit_struct(self, name: &'static str) -> Result<(), Error> {
+        if let Some(readable) = probe_readable(name) {
+            assert_next_token!(self, AssertHumanReadable(readable));
+            return Ok(());
+        }
+        if self.next_is_wildcard(Token::Any, || Token::UnitStruct { name })? {
+            return Ok(());
+        }
+        self.check_names(&[name])?;
         assert_next_token!(self, UnitStruct { name });
         Ok(())
     }
@@ -175,12 +860,16 @@ impl<'s, 'a> ser::Serializer for &'s mut Serializer<'a> {
     fn serialize_unit_variant(
         self,
         name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         variant: &'static str,
     ) -> Result<(), Error> {
-        if self.tokens.first() == Some(&Token::Enum { name }) {
+        if self.next_is_wildcard(Token::Any, || Token::UnitVariant { name, variant })? {
+            return Ok(());
+        }
+        self.check_names(&[name, variant])?;
+        if self.next_is_enum(name) {
             self.next_token();
-            assert_next_token!(self, Str(variant));
+            self.serialize_variant_identifier(variant_index, variant)?;
             assert_next_token!(self, Unit);
         } else {
             assert_next_token!(self, UnitVariant { name, variant });
@@ -192,30 +881,35 @@ impl<'s, 'a> ser::Serializer for &'s mut Serializer<'a> {
     where
         T: ?Sized + Serialize,
     {
+        self.check_names(&[name])?;
         assert_next_token!(self, NewtypeStruct { name });
-        value.serialize(self)
+        self.serialize_nested(value)
     }
 
     fn serialize_newtype_variant<T>(
         self,
         name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         variant: &'static str,
         value: &T,
     ) -> Result<(), Error>
     where
         T: ?Sized + Serialize,
     {
-        if self.tokens.first() == Some(&Token::Enum { name }) {
+        self.check_names(&[name, variant])?;
+        if self.next_is_enum(name) {
             self.next_token();
-            assert_next_token!(self, Str(variant));
+            self.serialize_variant_identifier(variant_index, variant)?;
         } else {
             assert_next_token!(self, NewtypeVariant { name, variant });
         }
-        value.serialize(self)
+        self.serialize_nested(value)
     }
 
     fn serialize_none(self) -> Result<(), Error> {
+        if self.next_is_wildcard(Token::Any, || Token::None)? {
+            return Ok(());
+        }
         assert_next_token!(self, None);
         Ok(())
     }
@@ -225,42 +919,55 @@ impl<'s, 'a> ser::Serializer for &'s mut Serializer<'a> {
         T: ?Sized + Serialize,
     {
         assert_next_token!(self, Some);
-        value.serialize(self)
+        self.serialize_nested(value)
     }
 
     fn serialize_seq(self, len: Option<usize>) -> Result<Self, Error> {
         assert_next_token!(self, Seq { len });
+        self.open_len("serialize_seq", len);
+        self.open_unordered(1)?;
+        self.open_elements();
         Ok(self)
     }
 
     fn serialize_tuple(self, len: usize) -> Result<Self, Error> {
         assert_next_token!(self, Tuple { len });
+        self.open_len("serialize_tuple", Some(len));
+        self.open_elements();
         Ok(self)
     }
 
     fn serialize_tuple_struct(self, name: &'static str, len: usize) -> Result<Self, Error> {
+        self.check_names(&[name])?;
         assert_next_token!(self, TupleStruct { name, len });
+        self.open_len("serialize_tuple_struct", Some(len));
+        self.open_elements();
         Ok(self)
     }
 
     fn serialize_tuple_variant(
         self,
         name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleVariant, Error> {
-        if self.tokens.first() == Some(&Token::Enum { name }) {
+        self.check_names(&[name, variant])?;
+        if self.next_is_enum(name) {
             self.next_token();
-            assert_next_token!(self, Str(variant));
+            self.serialize_variant_identifier(variant_index, variant)?;
             let len = Some(len);
             assert_next_token!(self, Seq { len });
+            self.open_len("serialize_tuple_variant", len);
+            self.open_elements();
             Ok(Variant {
                 ser: self,
                 end: Token::SeqEnd,
             })
         } else {
             assert_next_token!(self, TupleVariant { name, variant, len });
+            self.open_len("serialize_tuple_variant", Some(len));
+            self.open_elements();
             Ok(Variant {
                 ser: self,
                 end: Token::TupleVariantEnd,
@@ -270,32 +977,43 @@ impl<'s, 'a> ser::Serializer for &'s mut Serializer<'a> {
 
     fn serialize_map(self, len: Option<usize>) -> Result<Self, Error> {
         assert_next_token!(self, Map { len });
+        self.open_len("serialize_map", len);
+        self.open_unordered(2)?;
+        self.open_keys();
         Ok(self)
     }
 
     fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self, Error> {
+        self.check_names(&[name])?;
         assert_next_token!(self, Struct { name, len });
+        self.open_len("serialize_struct", Some(len));
+        self.open_keys();
         Ok(self)
     }
 
     fn serialize_struct_variant(
         self,
         name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeStructVariant, Error> {
-        if self.tokens.first() == Some(&Token::Enum { name }) {
+        self.check_names(&[name, variant])?;
+        if self.next_is_enum(name) {
             self.next_token();
-            assert_next_token!(self, Str(variant));
+            self.serialize_variant_identifier(variant_index, variant)?;
             let len = Some(len);
             assert_next_token!(self, Map { len });
+            self.open_len("serialize_struct_variant", len);
+            self.open_keys();
             Ok(Variant {
                 ser: self,
                 end: Token::MapEnd,
             })
         } else {
             assert_next_token!(self, StructVariant { name, variant, len });
+            self.open_len("serialize_struct_variant", Some(len));
+            self.open_keys();
             Ok(Variant {
                 ser: self,
                 end: Token::StructVariantEnd,
@@ -303,7 +1021,22 @@ impl<'s, 'a> ser::Serializer for &'s mut Serializer<'a> {
         }
     }
 
+    fn collect_str<T>(self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Display,
+    {
+        let v = value.to_string();
+        if let Some(&Token::CollectStr(_)) = self.tokens.first() {
+            assert_next_token!(self, CollectStr(v.as_str()));
+            return Ok(());
+        }
+        self.serialize_str(&v)
+    }
+
     fn is_human_readable(&self) -> bool {
+        if let Some(readable) = self.config.human_readable {
+            return readable;
+        }
         panic!(
             "Types which have different human-readable and compact representations \
              must explicitly mark their test cases with `serde_test::Configure`"
@@ -324,10 +1057,18 @@ impl<'a> ser::SerializeSeq for &mut Serializer<'a> {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        self.count_len();
+        if self.unordered.is_some() {
+            return self.serialize_unordered_element(value);
+        }
+        self.enter_element();
+        self.serialize_in(value)
     }
 
     fn end(self) -> Result<(), Error> {
+        self.close_len()?;
+        self.close_unordered()?;
+        self.close_elements();
         assert_next_token!(self, SeqEnd);
         Ok(())
     }
@@ -341,10 +1082,14 @@ impl<'a> ser::SerializeTuple for &mut Serializer<'a> {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        self.count_len();
+        self.enter_element();
+        self.serialize_in(value)
     }
 
     fn end(self) -> Result<(), Error> {
+        self.close_len()?;
+        self.close_elements();
         assert_next_token!(self, TupleEnd);
         Ok(())
     }
@@ -358,10 +1103,14 @@ impl<'a> ser::SerializeTupleStruct for &mut Serializer<'a> {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        self.count_len();
+        self.enter_element();
+        self.serialize_in(value)
     }
 
     fn end(self) -> Result<(), Error> {
+        self.close_len()?;
+        self.close_elements();
         assert_next_token!(self, TupleStructEnd);
         Ok(())
     }
@@ -375,10 +1124,14 @@ impl<'s, 'a> ser::SerializeTupleVariant for Variant<'s, 'a> {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut *self.ser)
+        self.ser.count_len();
+        self.ser.enter_element();
+        self.ser.serialize_in(value)
     }
 
     fn end(self) -> Result<(), Error> {
+        self.ser.close_len()?;
+        self.ser.close_elements();
         match self.end {
             Token::TupleVariantEnd => assert_next_token!(self.ser, TupleVariantEnd),
             Token::SeqEnd => assert_next_token!(self.ser, SeqEnd),
@@ -396,17 +1149,32 @@ impl<'a> ser::SerializeMap for &mut Serializer<'a> {
     where
         T: ?Sized + Serialize,
     {
-        key.serialize(&mut **self)
+        self.count_len();
+        if self.unordered.is_some() {
+            return self.serialize_unordered_key(key);
+        }
+        let before = self.tokens;
+        key.serialize(&mut **self)?;
+        self.check_key(before)?;
+        self.check_map_key()
     }
 
     fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        if self.unordered.is_some() {
+            return self.serialize_unordered_value(value);
+        }
+        let key = self.last_key;
+        self.path.push_key(key);
+        self.serialize_in(value)
     }
 
     fn end(self) -> Result<(), Self::Error> {
+        self.close_len()?;
+        self.close_unordered()?;
+        self.close_keys();
         assert_next_token!(self, MapEnd);
         Ok(())
     }
@@ -420,11 +1188,24 @@ impl<'a> ser::SerializeStruct for &mut Serializer<'a> {
     where
         T: ?Sized + Serialize,
     {
-        key.serialize(&mut **self)?;
-        value.serialize(&mut **self)
+        self.check_names(&[key])?;
+        self.count_len();
+        let before = self.tokens;
+        self.serialize_field_key(key)?;
+        self.check_key(before)?;
+        self.path.push_field(key);
+        self.serialize_in(value)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), Self::Error> {
+        self.check_names(&[key])?;
+        self.skipped += 1;
+        Ok(())
     }
 
     fn end(self) -> Result<(), Self::Error> {
+        self.close_len()?;
+        self.close_keys();
         assert_next_token!(self, StructEnd);
         Ok(())
     }
@@ -438,11 +1219,24 @@ impl<'s, 'a> ser::SerializeStructVariant for Variant<'s, 'a> {
     where
         T: ?Sized + Serialize,
     {
-        key.serialize(&mut *self.ser)?;
-        value.serialize(&mut *self.ser)
+        self.ser.check_names(&[key])?;
+        self.ser.count_len();
+        let before = self.ser.tokens;
+        self.ser.serialize_field_key(key)?;
+        self.ser.check_key(before)?;
+        self.ser.path.push_field(key);
+        self.ser.serialize_in(value)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), Self::Error> {
+        self.ser.check_names(&[key])?;
+        self.ser.skipped += 1;
+        Ok(())
     }
 
     fn end(self) -> Result<(), Self::Error> {
+        self.ser.close_len()?;
+        self.ser.close_keys();
         match self.end {
             Token::StructVariantEnd => assert_next_token!(self.ser, StructVariantEnd),
             Token::MapEnd => assert_next_token!(self.ser, MapEnd),
@@ -1,6 +1,10 @@
-use std::fmt::{self, Debug, Display};
+use std::ascii;
+use std::fmt::{self, Debug, Display, Write};
 
+/// New kinds of token are added in minor releases, so a `match` on `Token`
+/// outside this crate needs a wildcard arm.
 #[derive(Copy, Clone, PartialEq, Debug)]
+#[non_exhaustive]
 pub enum Token {
     /// A serialized `bool`.
     ///
@@ -146,7 +150,52 @@ pub enum Token {
     /// A borrowed `[u8]`.
     BorrowedBytes(&'static [u8]),
 
-    /// A serialized `ByteBuf`
+    /// A serialized `ByteBuf`.
+    ///
+    /// Serializing matches `serialize_bytes` like `Bytes` does. Deserializing
+    /// hands the `Deserialize` impl an owned buffer through `visit_byte_buf`,
+    /// which shows up in [`Report::owned_bytes`].
+    ///
+    /// [`Report::owned_bytes`]: crate::Report::owned_bytes
+    ///
+    /// ```
+    /// # use serde::de::{self, Deserialize, Deserializer, Visitor};
+    /// # use serde_test::{assert_de_tokens_with_config, Config, Token};
+    /// # use std::fmt;
+    /// #
+    /// #[derive(PartialEq, Debug)]
+    /// struct Buf(Vec<u8>);
+    ///
+    /// impl<'de> Deserialize<'de> for Buf {
+    ///     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    ///     where
+    ///         D: Deserializer<'de>,
+    ///     {
+    ///         struct BufVisitor;
+    ///
+    ///         impl<'de> Visitor<'de> for BufVisitor {
+    ///             type Value = Buf;
+    ///
+    ///             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    ///                 formatter.write_str("an owned byte buffer")
+    ///             }
+    ///
+    ///             fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Buf, E> {
+    ///                 Ok(Buf(v))
+    ///             }
+    ///         }
+    ///
+    ///         deserializer.deserialize_byte_buf(BufVisitor)
+    ///     }
+    /// }
+    ///
+    /// let report = assert_de_tokens_with_config(
+    ///     &Buf(vec![1, 2]),
+    ///     &[Token::ByteBuf(&[1, 2])],
+    ///     Config::new(),
+    /// );
+    /// assert_eq!(report.owned_bytes(), 1);
+    /// ```
     ByteBuf(&'static [u8]),
 
     /// A serialized `Option<T>` containing none.
@@ -241,6 +290,25 @@ pub enum Token {
     /// );
     /// # }
     /// ```
+    ///
+    /// The `name` may be given as `"*"` in order to match any type name, here
+    /// and in every other struct, enum and enum variant token. This lets tests
+    /// of generic code share one expected stream across many concrete types.
+    ///
+    /// ```
+    /// # use serde_derive::{Deserialize, Serialize};
+    /// # use serde_test::{assert_tokens, Token};
+    /// #
+    /// # fn main() {
+    /// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    /// struct Wrapper<T>(T);
+    ///
+    /// assert_tokens(
+    ///     &Wrapper(0u8),
+    ///     &[Token::NewtypeStruct { name: "*" }, Token::U8(0)],
+    /// );
+    /// # }
+    /// ```
     NewtypeStruct { name: &'static str },
 
     /// The header to a newtype variant of an enum.
@@ -552,10 +620,609 @@ pub enum Token {
     /// # }
     /// ```
     Enum { name: &'static str },
+
+    /// Matches any single scalar value during serialization, for fields whose
+    /// value differs from run to run, such as a timestamp or a random id.
+    ///
+    /// A scalar is anything that serializes as one token: a bool, number,
+    /// char, string, bytes, unit, unit struct, unit variant or `None`. The
+    /// token cannot be deserialized.
+    ///
+    /// ```
+    /// # use serde_derive::Serialize;
+    /// # use serde_test::{assert_ser_tokens, Token};
+    /// # use std::time::{SystemTime, UNIX_EPOCH};
+    /// #
+    /// #[derive(Serialize)]
+    /// struct Event {
+    ///     kind: &'static str,
+    ///     at: u64,
+    /// }
+    ///
+    /// let at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    /// assert_ser_tokens(
+    ///     &Event { kind: "login", at },
+    ///     &[
+    ///         Token::Struct { name: "Event", len: 2 },
+    ///         Token::Str("kind"),
+    ///         Token::Str("login"),
+    ///         Token::Str("at"),
+    ///         Token::Any,
+    ///         Token::StructEnd,
+    ///     ],
+    /// );
+    /// ```
+    Any,
+
+    /// Matches any single integer or float during serialization, whatever
+    /// its width or value.
+    ///
+    /// A Serialize impl may pick `serialize_u32` on one platform and
+    /// `serialize_u64` on another, such as for a `usize`, without breaking
+    /// the test. The token cannot be deserialized.
+    ///
+    /// ```
+    /// # use serde_test::{assert_ser_tokens, Token};
+    /// #
+    /// assert_ser_tokens(&[1u32, 2], &[
+    ///     Token::Tuple { len: 2 },
+    ///     Token::AnyNumber,
+    ///     Token::AnyNumber,
+    ///     Token::TupleEnd,
+    /// ]);
+    /// assert_ser_tokens(&7usize, &[Token::AnyNumber]);
+    /// ```
+    AnyNumber,
+
+    /// Matches any single string during serialization, whatever its
+    /// contents. The token cannot be deserialized.
+    ///
+    /// ```
+    /// # use serde_test::{assert_ser_tokens, Token};
+    /// #
+    /// assert_ser_tokens(&"generated-id-1234", &[Token::AnyStr]);
+    /// ```
+    AnyStr,
+
+    /// Matches one whole value during serialization, however many tokens it
+    /// would take, so that a test can check some fields of a large value and
+    /// ignore the rest.
+    ///
+    /// The value may be a scalar or a compound such as a struct, map or
+    /// sequence together with everything nested inside it. It stands in for
+    /// a nested value: an element, a field or map value, or the content of a
+    /// newtype or `Some`. The token cannot be deserialized.
+    ///
+    /// ```
+    /// # use serde_derive::Serialize;
+    /// # use serde_test::{assert_ser_tokens, Token};
+    /// # use std::collections::BTreeMap;
+    /// #
+    /// #[derive(Serialize)]
+    /// struct Response {
+    ///     status: u16,
+    ///     headers: BTreeMap<&'static str, &'static str>,
+    ///     body: Vec<u8>,
+    /// }
+    ///
+    /// let mut headers = BTreeMap::new();
+    /// headers.insert("content-type", "text/plain");
+    /// headers.insert("server", "test");
+    ///
+    /// assert_ser_tokens(
+    ///     &Response {
+    ///         status: 200,
+    ///         headers,
+    ///         body: b"ok".to_vec(),
+    ///     },
+    ///     &[
+    ///         Token::Struct { name: "Response", len: 3 },
+    ///         Token::Str("status"),
+    ///         Token::U16(200),
+    ///         Token::Str("headers"),
+    ///         Token::SkipValue,
+    ///         Token::Str("body"),
+    ///         Token::SkipValue,
+    ///         Token::StructEnd,
+    ///     ],
+    /// );
+    /// ```
+    SkipValue,
+
+    /// Starts a group of entries that a sequence or map may serialize in any
+    /// order, for types such as `HashMap` and `HashSet` whose iteration order
+    /// is unspecified. The group is closed by `Token::UnorderedEnd`.
+    ///
+    /// The group directly follows a `Token::Seq` or `Token::Map` header and
+    /// holds all of its contents. Each serialized element, or key and value
+    /// of a map, must match one entry of the group, and every entry must be
+    /// matched by the time the sequence or map ends. When deserializing, both
+    /// markers are skipped and the entries are fed in the order written.
+    ///
+    /// ```
+    /// # use serde_test::{assert_tokens, Token};
+    /// # use std::collections::HashMap;
+    /// #
+    /// let mut map = HashMap::new();
+    /// map.insert('a', 1);
+    /// map.insert('b', 2);
+    /// map.insert('c', 3);
+    ///
+    /// assert_tokens(
+    ///     &map,
+    ///     &[
+    ///         Token::Map { len: Some(3) },
+    ///         Token::UnorderedStart,
+    ///         Token::Char('a'),
+    ///         Token::I32(1),
+    ///         Token::Char('b'),
+    ///         Token::I32(2),
+    ///         Token::Char('c'),
+    ///         Token::I32(3),
+    ///         Token::UnorderedEnd,
+    ///         Token::MapEnd,
+    ///     ],
+    /// );
+    /// ```
+    UnorderedStart,
+
+    /// Ends a group of entries started by `Token::UnorderedStart`.
+    UnorderedEnd,
+
+    /// Matches a single scalar during serialization if the predicate
+    /// registered under this name with [`Config::verify`] accepts the token
+    /// it serializes to, for values that are constrained but not fully
+    /// deterministic, such as a string with a known prefix or an integer in
+    /// some range.
+    ///
+    /// The predicate sees the token the scalar would be written as with no
+    /// configuration, such as `Token::Str` for any string. The token cannot be
+    /// deserialized.
+    ///
+    /// [`Config::verify`]: crate::Config::verify
+    ///
+    /// ```
+    /// # use serde_derive::Serialize;
+    /// # use serde_test::{assert_ser_tokens_with_config, Config, Token};
+    /// #
+    /// #[derive(Serialize)]
+    /// struct Session {
+    ///     id: String,
+    ///     ttl: u32,
+    /// }
+    ///
+    /// fn is_session_id(token: &Token) -> bool {
+    ///     matches!(token, Token::Str(id) if id.starts_with("sess-"))
+    /// }
+    ///
+    /// fn is_short_ttl(token: &Token) -> bool {
+    ///     matches!(token, Token::U32(1..=60))
+    /// }
+    ///
+    /// let config = Config::new()
+    ///     .verify("session id", is_session_id)
+    ///     .verify("short ttl", is_short_ttl);
+    ///
+    /// assert_ser_tokens_with_config(
+    ///     &Session {
+    ///         id: "sess-4f1c".to_owned(),
+    ///         ttl: 30,
+    ///     },
+    ///     &[
+    ///         Token::Struct { name: "Session", len: 2 },
+    ///         Token::Str("id"),
+    ///         Token::Verify("session id"),
+    ///         Token::Str("ttl"),
+    ///         Token::Verify("short ttl"),
+    ///         Token::StructEnd,
+    ///     ],
+    ///     config,
+    /// );
+    /// ```
+    Verify(&'static str),
+
+    /// The index of an enum variant, following a `Token::Enum` in place of
+    /// the variant's name.
+    ///
+    /// Many binary formats identify variants by index. When deserializing,
+    /// the variant identifier is visited with `visit_u32` instead of
+    /// `visit_str`, which exercises the index-based path of a `Deserialize`
+    /// impl. When serializing, the index passed to the serializer must match.
+    ///
+    /// ```
+    /// # use serde_derive::{Deserialize, Serialize};
+    /// # use serde_test::{assert_tokens, Token};
+    /// #
+    /// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    /// enum E {
+    ///     A,
+    ///     B(u8),
+    /// }
+    ///
+    /// assert_tokens(
+    ///     &E::B(0),
+    ///     &[
+    ///         Token::Enum { name: "E" },
+    ///         Token::VariantIndex(1),
+    ///         Token::U8(0),
+    ///     ],
+    /// );
+    /// ```
+    VariantIndex(u32),
+
+    /// The name of a struct field or enum variant, delivered as bytes.
+    ///
+    /// Some formats hand identifiers to the visitor as bytes. When
+    /// deserializing, the name is visited with `visit_bytes` instead of
+    /// `visit_str`, which exercises the bytes path of a field or variant
+    /// identifier. When serializing, it matches a struct field key or, after
+    /// a `Token::Enum`, a variant name.
+    ///
+    /// ```
+    /// # use serde_derive::{Deserialize, Serialize};
+    /// # use serde_test::{assert_tokens, Token};
+    /// #
+    /// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    /// enum E {
+    ///     V { a: u8 },
+    /// }
+    ///
+    /// assert_tokens(
+    ///     &E::V { a: 0 },
+    ///     &[
+    ///         Token::Enum { name: "E" },
+    ///         Token::BytesIdentifier("V"),
+    ///         Token::Map { len: Some(1) },
+    ///         Token::BytesIdentifier("a"),
+    ///         Token::U8(0),
+    ///         Token::MapEnd,
+    ///     ],
+    /// );
+    /// ```
+    BytesIdentifier(&'static str),
+
+    /// Checks, at the point of a [`HumanReadableProbe`] in the value, whether
+    /// the serializer or deserializer reports itself as human-readable.
+    ///
+    /// This verifies that a `Configure`-style wrapper does not lose the flag
+    /// while recursing. The token matches nothing but a probe.
+    ///
+    /// [`HumanReadableProbe`]: crate::HumanReadableProbe
+    ///
+    /// ```
+    /// # use serde_test::{assert_tokens, Configure, HumanReadableProbe, Token};
+    /// # use std::collections::BTreeMap;
+    /// #
+    /// let mut map = BTreeMap::new();
+    /// map.insert("key".to_owned(), HumanReadableProbe);
+    ///
+    /// assert_tokens(
+    ///     &map.readable(),
+    ///     &[
+    ///         Token::Map { len: Some(1) },
+    ///         Token::Str("key"),
+    ///         Token::AssertHumanReadable(true),
+    ///         Token::MapEnd,
+    ///     ],
+    /// );
+    /// ```
+    AssertHumanReadable(bool),
+
+    /// Makes the deserializer fail with the given message when it reaches
+    /// this token, for testing how a `Deserialize` impl propagates or wraps
+    /// an error from the middle of a sequence or map.
+    ///
+    /// ```
+    /// # use serde_test::{assert_de_tokens_error, Token};
+    /// #
+    /// assert_de_tokens_error::<Vec<u8>>(
+    ///     &[
+    ///         Token::Seq { len: Some(2) },
+    ///         Token::U8(1),
+    ///         Token::Error("connection reset"),
+    ///     ],
+    ///     "connection reset",
+    /// );
+    /// ```
+    Error(&'static str),
+
+    /// A string that the `Serialize` impl must produce through
+    /// `Serializer::collect_str`, rather than by passing a `&str` to
+    /// `serialize_str`.
+    ///
+    /// An expected `Token::Str` accepts either way of serializing a string.
+    /// During deserialization this token behaves like `Token::Str`.
+    ///
+    /// ```
+    /// # use serde::{Serialize, Serializer};
+    /// # use serde_test::{assert_ser_tokens, Token};
+    /// # use std::net::Ipv4Addr;
+    /// #
+    /// struct Host(Ipv4Addr);
+    ///
+    /// impl Serialize for Host {
+    ///     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    ///     where
+    ///         S: Serializer,
+    ///     {
+    ///         serializer.collect_str(&self.0)
+    ///     }
+    /// }
+    ///
+    /// assert_ser_tokens(&Host(Ipv4Addr::LOCALHOST), &[Token::CollectStr("127.0.0.1")]);
+    /// ```
+    CollectStr(&'static str),
 }
 
+/// Writes the token as the Rust expression that constructs it, such as
+/// `Token::Struct { name: "Foo", len: 2 }`, so that a stream printed by a
+/// failing test can be pasted back into the test.
+///
+/// ```
+/// # use serde_test::Token;
+/// #
+/// assert_eq!(Token::Bytes(b"a\0").to_string(), r#"Token::Bytes(b"a\x00")"#);
+/// assert_eq!(Token::F64(f64::NAN).to_string(), "Token::F64(f64::NAN)");
+/// ```
 impl Display for Token {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        Debug::fmt(self, formatter)
+        formatter.write_str("Token::")?;
+        match *self {
+            Token::F32(v) if !v.is_finite() => {
+                write!(formatter, "F32(f32::{})", non_finite_name(f64::from(v)))
+            }
+            Token::F64(v) if !v.is_finite() => {
+                write!(formatter, "F64(f64::{})", non_finite_name(v))
+            }
+            Token::Bytes(v) => write!(formatter, "Bytes({})", ByteStr(v)),
+            Token::BorrowedBytes(v) => write!(formatter, "BorrowedBytes({})", ByteStr(v)),
+            Token::ByteBuf(v) => write!(formatter, "ByteBuf({})", ByteStr(v)),
+            _ => Debug::fmt(self, formatter),
+        }
+    }
+}
+
+macro_rules! from_primitive {
+    ($($(#[$attr:meta])* $ty:ty => $variant:ident,)*) => {
+        $(
+            $(#[$attr])*
+            impl From<$ty> for Token {
+                #[doc = concat!("Converts to `Token::", stringify!($variant), "`.")]
+                fn from(v: $ty) -> Token {
+                    Token::$variant(v)
+                }
+            }
+        )*
+    };
+}
+
+from_primitive! {
+    /// Primitives convert into the token they serialize as, which lets
+    /// builders and helper macros accept plain literals.
+    ///
+    /// ```
+    /// # use serde_test::Token;
+    /// #
+    /// let tokens: Vec<Token> = vec![1u8.into(), "a".into(), true.into()];
+    /// assert_eq!(tokens, [Token::U8(1), Token::Str("a"), Token::Bool(true)]);
+    /// ```
+    bool => Bool,
+    i8 => I8,
+    i16 => I16,
+    i32 => I32,
+    i64 => I64,
+    u8 => U8,
+    u16 => U16,
+    u32 => U32,
+    u64 => U64,
+    f32 => F32,
+    f64 => F64,
+    char => Char,
+    &'static str => Str,
+    &'static [u8] => Bytes,
+}
+
+fn non_finite_name(v: f64) -> &'static str {
+    if v.is_nan() {
+        "NAN"
+    } else if v > 0.0 {
+        "INFINITY"
+    } else {
+        "NEG_INFINITY"
+    }
+}
+
+/// A byte string literal, `b"..."`.
+struct ByteStr(&'static [u8]);
+
+impl Display for ByteStr {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("b\"")?;
+        for &byte in self.0 {
+            for escaped in ascii::escape_default(byte) {
+                formatter.write_char(escaped as char)?;
+            }
+        }
+        formatter.write_str("\"")
+    }
+}
+
+/// Returns `tokens` with each string token written as `Token::Str` and each
+/// bytes token written as `Token::Bytes`.
+///
+/// Two streams that differ only in whether a string or bytes value was
+/// borrowed, transient or owned compare equal once canonicalized, for tests
+/// that care about the values and not about how they were handed over.
+///
+/// ```
+/// # use serde_test::{canonicalize, Token};
+/// #
+/// let borrowed = [Token::BorrowedStr("a"), Token::ByteBuf(b"b")];
+/// let transient = [Token::Str("a"), Token::Bytes(b"b")];
+/// assert_ne!(borrowed, transient);
+/// assert_eq!(canonicalize(&borrowed), canonicalize(&transient));
+/// ```
+#[must_use]
+pub fn canonicalize(tokens: &[Token]) -> Vec<Token> {
+    tokens
+        .iter()
+        .map(|&token| match token {
+            Token::BorrowedStr(s) | Token::String(s) | Token::CollectStr(s) => Token::Str(s),
+            Token::BorrowedBytes(b) | Token::ByteBuf(b) => Token::Bytes(b),
+            token => token,
+        })
+        .collect()
+}
+
+/// Formats `tokens` as a Rust slice literal with one token per line, which
+/// can be pasted into a test as its expected stream.
+///
+/// ```
+/// # use serde_test::{to_tokens, tokens_literal};
+/// #
+/// let literal = tokens_literal(&to_tokens(&(1u8, "a")));
+/// assert_eq!(
+///     literal,
+///     r#"&[
+///     Token::Tuple { len: 2 },
+///     Token::U8(1),
+///     Token::Str("a"),
+///     Token::TupleEnd,
+/// ]"#,
+/// );
+/// ```
+#[must_use]
+pub fn tokens_literal(tokens: &[Token]) -> String {
+    let mut literal = String::from("&[\n");
+    for token in tokens {
+        let _ = writeln!(literal, "    {},", token);
+    }
+    literal.push(']');
+    literal
+}
+
+/// Whether the token is the header of a compound value that is closed by a
+/// matching end token.
+pub(crate) fn opens_compound(token: Token) -> bool {
+    matches!(
+        token,
+        Token::Seq { .. }
+            | Token::Tuple { .. }
+            | Token::TupleStruct { .. }
+            | Token::TupleVariant { .. }
+            | Token::Map { .. }
+            | Token::Struct { .. }
+            | Token::StructVariant { .. }
+    )
+}
+
+/// Whether the token is the end of a compound value.
+pub(crate) fn closes_compound(token: Token) -> bool {
+    matches!(
+        token,
+        Token::SeqEnd
+            | Token::TupleEnd
+            | Token::TupleStructEnd
+            | Token::TupleVariantEnd
+            | Token::MapEnd
+            | Token::StructEnd
+            | Token::StructVariantEnd
+    )
+}
+
+/// The number of tokens making up the first complete value in `tokens`.
+pub(crate) fn value_len(tokens: &[Token]) -> usize {
+    match tokens.first() {
+        None => 0,
+        Some(Token::Some | Token::NewtypeStruct { .. } | Token::NewtypeVariant { .. }) => {
+            1 + value_len(&tokens[1..])
+        }
+        Some(Token::Enum { .. }) => {
+            let variant = 1 + value_len(&tokens[1..]);
+            variant + value_len(&tokens[variant..])
+        }
+        Some(&token) if opens_compound(token) => {
+            let mut depth = 0;
+            for (i, &token) in tokens.iter().enumerate() {
+                if opens_compound(token) {
+                    depth += 1;
+                } else if closes_compound(token) {
+                    depth -= 1;
+                    if depth == 0 {
+                        return i + 1;
+                    }
+                }
+            }
+            tokens.len()
+        }
+        Some(_) => 1,
+    }
+}
+
+/// Compares a name from the expected stream against the name that
+/// serialization or deserialization used, treating `"*"` as a wildcard.
+#[must_use]
+pub fn name_matches(expected: &str, actual: &str) -> bool {
+    expected == "*" || expected == actual
+}
+
+/// Compares a token from the expected stream against the token that
+/// serialization or deserialization produced, honoring wildcard names.
+#[allow(clippy::many_single_char_names)]
+#[must_use]
+pub fn token_matches(expected: Token, actual: Token) -> bool {
+    match (expected, actual) {
+        (Token::UnitStruct { name: a }, Token::UnitStruct { name: b })
+        | (Token::NewtypeStruct { name: a }, Token::NewtypeStruct { name: b })
+        | (Token::Enum { name: a }, Token::Enum { name: b }) => name_matches(a, b),
+        (Token::TupleStruct { name: a, len: m }, Token::TupleStruct { name: b, len: n })
+        | (Token::Struct { name: a, len: m }, Token::Struct { name: b, len: n }) => {
+            name_matches(a, b) && m == n
+        }
+        (
+            Token::UnitVariant {
+                name: a,
+                variant: v,
+            },
+            Token::UnitVariant {
+                name: b,
+                variant: w,
+            },
+        )
+        | (
+            Token::NewtypeVariant {
+                name: a,
+                variant: v,
+            },
+            Token::NewtypeVariant {
+                name: b,
+                variant: w,
+            },
+        ) => name_matches(a, b) && v == w,
+        (
+            Token::TupleVariant {
+                name: a,
+                variant: v,
+                len: m,
+            },
+            Token::TupleVariant {
+                name: b,
+                variant: w,
+                len: n,
+            },
+        )
+        | (
+            Token::StructVariant {
+                name: a,
+                variant: v,
+                len: m,
+            },
+            Token::StructVariant {
+                name: b,
+                variant: w,
+                len: n,
+            },
+        ) => name_matches(a, b) && v == w && m == n,
+        (expected, actual) => expected == actual,
     }
 }
@@ -0,0 +1,529 @@
+use crate::assert::{assert_de_tokens_with_config, assert_ser_tokens_with_config};
+use crate::config::Config;
+use crate::report::Report;
+use crate::token::{closes_compound, opens_compound, value_len, Token};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Debug};
+use std::ops::{Deref, Index, Range};
+
+/// An expected token stream split into named sections.
+///
+/// A failing assertion made through a `Tokens` names the section in which
+/// the stream diverged and lists only the tokens of that section, which keeps
+/// failures in long protocol-style streams readable. A `Tokens` also
+/// dereferences to the whole stream, so it can be passed to any of the
+/// assertion functions as a plain slice.
+///
+/// ```should_panic
+/// # use serde_test::{Token, Tokens};
+/// #
+/// let tokens = Tokens::new()
+///     .section("header", &[Token::Tuple { len: 3 }, Token::U8(1)])
+///     .section("body", &[Token::U8(2), Token::U8(3), Token::TupleEnd]);
+///
+/// // Panics with a message naming the "body" section.
+/// tokens.assert_ser_tokens(&(1u8, 2u8, 4u8));
+/// ```
+///
+/// The `Debug` output lists one token per line, indented by nesting depth,
+/// and individual values can be picked out of the stream by their path.
+///
+/// ```
+/// # use serde_test::{Token, Tokens};
+/// #
+/// let tokens = Tokens::from(vec![
+///     Token::Struct { name: "Config", len: 1 },
+///     Token::Str("ports"),
+///     Token::Seq { len: Some(2) },
+///     Token::U16(80),
+///     Token::U16(443),
+///     Token::SeqEnd,
+///     Token::StructEnd,
+/// ]);
+///
+/// assert_eq!(tokens["ports[1]"], [Token::U16(443)]);
+/// assert_eq!(tokens.get("ports[2]"), None);
+/// assert_eq!(
+///     format!("{:?}", tokens),
+///     "\
+/// [
+///     Struct { name: \"Config\", len: 1 },
+///         Str(\"ports\"),
+///         Seq { len: Some(2) },
+///             U16(80),
+///             U16(443),
+///         SeqEnd,
+///     StructEnd,
+/// ]",
+/// );
+/// ```
+#[derive(Clone, Default)]
+pub struct Tokens {
+    tokens: Vec<Token>,
+    sections: Vec<Section>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Section {
+    pub(crate) name: String,
+    pub(crate) range: Range<usize>,
+}
+
+impl Tokens {
+    /// Creates an empty stream.
+    #[must_use]
+    pub fn new() -> Self {
+        Tokens::default()
+    }
+
+    /// Joins several runs of tokens into one stream.
+    #[must_use]
+    pub fn concat(parts: &[&[Token]]) -> Self {
+        Tokens::from(parts.concat())
+    }
+
+    /// A stream of `tokens` repeated `n` times.
+    ///
+    /// ```
+    /// # use serde_test::{assert_tokens, Token, Tokens};
+    /// #
+    /// let value = vec![0u8; 10_000];
+    /// let tokens = Tokens::concat(&[
+    ///     &[Token::Seq { len: Some(10_000) }],
+    ///     &Tokens::repeat(&[Token::U8(0)], 10_000),
+    ///     &[Token::SeqEnd],
+    /// ]);
+    /// assert_tokens(&value, &tokens);
+    /// ```
+    #[must_use]
+    pub fn repeat(tokens: &[Token], n: usize) -> Self {
+        Tokens::from(tokens.repeat(n))
+    }
+
+    /// A `Seq` of `len` elements, where `element(i)` gives the tokens of the
+    /// element at index `i`.
+    ///
+    /// ```
+    /// # use serde_test::{assert_tokens, Token, Tokens};
+    /// #
+    /// let value: Vec<u32> = (0..10_000).collect();
+    /// let tokens = Tokens::seq_of(10_000, |i| [Token::U32(i as u32)]);
+    /// assert_tokens(&value, &tokens);
+    /// ```
+    pub fn seq_of<F, I>(len: usize, mut element: F) -> Self
+    where
+        F: FnMut(usize) -> I,
+        I: IntoIterator<Item = Token>,
+    {
+        let mut tokens = vec![Token::Seq { len: Some(len) }];
+        for i in 0..len {
+            tokens.extend(element(i));
+        }
+        tokens.push(Token::SeqEnd);
+        Tokens::from(tokens)
+    }
+
+    /// Appends `tokens` to the stream as a section called `name`.
+    #[must_use]
+    pub fn section(mut self, name: &str, tokens: &[Token]) -> Self {
+        let start = self.tokens.len();
+        self.tokens.extend_from_slice(tokens);
+        self.sections.push(Section {
+            name: name.to_owned(),
+            range: start..self.tokens.len(),
+        });
+        self
+    }
+
+    /// Appends the tokens of `fragment` to the stream as a section named
+    /// after the fragment.
+    #[must_use]
+    pub fn splice(self, fragment: &TokenFragment) -> Self {
+        self.section(&fragment.name, &fragment.tokens)
+    }
+
+    /// Runs both `assert_ser_tokens` and `assert_de_tokens` against this
+    /// stream.
+    #[track_caller]
+    pub fn assert_tokens<'de, T>(&'de self, value: &T)
+    where
+        T: Serialize + Deserialize<'de> + PartialEq + Debug,
+    {
+        self.assert_ser_tokens(value);
+        self.assert_de_tokens(value);
+    }
+
+    /// Asserts that `value` serializes to this stream.
+    #[track_caller]
+    pub fn assert_ser_tokens<T>(&self, value: &T) -> Report
+    where
+        T: ?Sized + Serialize,
+    {
+        assert_ser_tokens_with_config(value, &self.tokens, self.config())
+    }
+
+    /// Asserts that this stream deserializes into `value`.
+    #[track_caller]
+    pub fn assert_de_tokens<'de, T>(&'de self, value: &T) -> Report
+    where
+        T: Deserialize<'de> + PartialEq + Debug,
+    {
+        assert_de_tokens_with_config(value, &self.tokens, self.config())
+    }
+
+    /// The tokens of the value at `path`, written the way failure messages
+    /// write paths: `servers[3].port`, or `headers["host"]` for a map entry.
+    ///
+    /// Options, newtypes and enum variants are looked through, so a path
+    /// addresses the contents of the value they wrap. Returns `None` if the
+    /// path does not name a value in the stream.
+    ///
+    /// ```
+    /// # use serde_test::{Token, Tokens};
+    /// #
+    /// let tokens = Tokens::from(vec![
+    ///     Token::Struct { name: "Config", len: 1 },
+    ///     Token::Str("ports"),
+    ///     Token::Seq { len: Some(2) },
+    ///     Token::U16(80),
+    ///     Token::U16(443),
+    ///     Token::SeqEnd,
+    ///     Token::StructEnd,
+    /// ]);
+    /// assert_eq!(tokens.get("ports[1]"), Some(&[Token::U16(443)][..]));
+    /// assert_eq!(tokens.get("ports[2]"), None);
+    ///
+    /// // A single token, or a header that is never closed, has no fields.
+    /// assert_eq!(Tokens::from(vec![Token::U8(1)]).get("a"), None);
+    /// let unclosed = Tokens::from(vec![Token::Seq { len: Some(1) }]);
+    /// assert_eq!(unclosed.get("[0]"), None);
+    /// ```
+    #[must_use]
+    pub fn get(&self, path: &str) -> Option<&[Token]> {
+        let mut value = &self.tokens[..value_len(&self.tokens)];
+        let mut rest = path;
+        let mut first = true;
+        while !rest.is_empty() {
+            let (segment, tail) = next_segment(rest, first)?;
+            value = child(value, &segment)?;
+            rest = tail;
+            first = false;
+        }
+        Some(value)
+    }
+
+    /// The tokens of the complete value that starts at `index`, such as a
+    /// whole struct from its `Struct` token through its `StructEnd`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    #[must_use]
+    pub fn value_at(&self, index: usize) -> &[Token] {
+        let tokens = &self.tokens[index..];
+        &tokens[..value_len(tokens).max(1)]
+    }
+
+    /// The tokens of the section called `name`, if there is one.
+    #[must_use]
+    pub fn section_tokens(&self, name: &str) -> Option<&[Token]> {
+        self.sections
+            .iter()
+            .find(|section| section.name == name)
+            .map(|section| &self.tokens[section.range.clone()])
+    }
+
+    fn config(&self) -> Config {
+        Config {
+            sections: self.sections.clone(),
+            ..Config::new()
+        }
+    }
+}
+
+impl From<Vec<Token>> for Tokens {
+    fn from(tokens: Vec<Token>) -> Self {
+        Tokens {
+            tokens,
+            sections: Vec::new(),
+        }
+    }
+}
+
+/// Collects a stream from a generator or a chain of fragments, ready to be
+/// passed to any of the assertion functions.
+///
+/// ```
+/// # use serde_test::{assert_tokens, Token, Tokens};
+/// # use std::iter;
+/// #
+/// let tokens: Tokens = iter::once(Token::Seq { len: Some(3) })
+///     .chain((1..=3).map(Token::U8))
+///     .chain(iter::once(Token::SeqEnd))
+///     .collect();
+/// assert_tokens(&vec![1u8, 2, 3], &tokens);
+/// ```
+impl FromIterator<Token> for Tokens {
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = Token>,
+    {
+        Tokens::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl Extend<Token> for Tokens {
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = Token>,
+    {
+        self.tokens.extend(iter);
+    }
+}
+
+impl<'a> IntoIterator for &'a Tokens {
+    type Item = &'a Token;
+    type IntoIter = std::slice::Iter<'a, Token>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.tokens.iter()
+    }
+}
+
+impl From<&[Token]> for Tokens {
+    fn from(tokens: &[Token]) -> Self {
+        Tokens::from(tokens.to_vec())
+    }
+}
+
+impl Index<&str> for Tokens {
+    type Output = [Token];
+
+    #[track_caller]
+    fn index(&self, path: &str) -> &[Token] {
+        match self.get(path) {
+            Some(value) => value,
+            None => panic!("no value at path `{}`", path),
+        }
+    }
+}
+
+impl Debug for Tokens {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if self.tokens.is_empty() {
+            return formatter.write_str("[]");
+        }
+        formatter.write_str("[\n")?;
+        let mut depth = 1;
+        for (i, &token) in self.tokens.iter().enumerate() {
+            if let Some(section) = self.sections.iter().find(|s| s.range.start == i) {
+                writeln!(
+                    formatter,
+                    "{:indent$}// {}",
+                    "",
+                    section.name,
+                    indent = depth * 4
+                )?;
+            }
+            if closes_compound(token) {
+                depth = depth.saturating_sub(1).max(1);
+            }
+            writeln!(formatter, "{:indent$}{:?},", "", token, indent = depth * 4)?;
+            if opens_compound(token) {
+                depth += 1;
+            }
+        }
+        formatter.write_str("]")
+    }
+}
+
+enum Segment<'p> {
+    Field(&'p str),
+    Index(usize),
+    Key(String),
+}
+
+/// Splits the first segment off `path`. A field name may appear without a
+/// leading `.` only at the start of the path.
+fn next_segment(path: &str, first: bool) -> Option<(Segment<'_>, &str)> {
+    if let Some(rest) = path.strip_prefix('[') {
+        if let Some(rest) = rest.strip_prefix('"') {
+            let mut key = String::new();
+            let mut chars = rest.char_indices();
+            while let Some((i, ch)) = chars.next() {
+                match ch {
+                    '"' => {
+                        let rest = rest[i + 1..].strip_prefix(']')?;
+                        return Some((Segment::Key(key), rest));
+                    }
+                    '\\' => key.push(chars.next()?.1),
+                    ch => key.push(ch),
+                }
+            }
+            return None;
+        }
+        let end = rest.find(']')?;
+        let index = rest[..end].parse().ok()?;
+        return Some((Segment::Index(index), &rest[end + 1..]));
+    }
+    let rest = match path.strip_prefix('.') {
+        Some(rest) => rest,
+        None if first => path,
+        None => return None,
+    };
+    let end = rest.find(|ch| ch == '.' || ch == '[').unwrap_or(rest.len());
+    if end == 0 {
+        return None;
+    }
+    Some((Segment::Field(&rest[..end]), &rest[end..]))
+}
+
+/// Finds the child of `value` named by `segment`.
+fn child<'t>(value: &'t [Token], segment: &Segment) -> Option<&'t [Token]> {
+    let (header, mut rest) = match unwrap(value) {
+        [header, rest @ .., end] if opens_compound(*header) && closes_compound(*end) => {
+            (*header, rest)
+        }
+        _ => return None,
+    };
+    match (header, segment) {
+        (
+            Token::Seq { .. }
+            | Token::Tuple { .. }
+            | Token::TupleStruct { .. }
+            | Token::TupleVariant { .. },
+            &Segment::Index(index),
+        ) => {
+            for _ in 0..index {
+                rest = rest.get(value_len(rest)..)?;
+            }
+            let len = value_len(rest);
+            if len == 0 {
+                None
+            } else {
+                Some(&rest[..len])
+            }
+        }
+        (Token::Struct { .. } | Token::StructVariant { .. } | Token::Map { .. }, _) => {
+            while !rest.is_empty() {
+                let key_len = value_len(rest);
+                let value_len = value_len(&rest[key_len..]);
+                let (key, value) = (&rest[..key_len], &rest[key_len..key_len + value_len]);
+                let found = match (key, segment) {
+                    (
+                        [Token::Str(key)
+                        | Token::BorrowedStr(key)
+                        | Token::String(key)
+                        | Token::BytesIdentifier(key)],
+                        Segment::Field(name),
+                    ) => key == name,
+                    (
+                        [Token::Str(key) | Token::BorrowedStr(key) | Token::String(key)],
+                        Segment::Key(name),
+                    ) => key == name,
+                    _ => false,
+                };
+                if found {
+                    return Some(value);
+                }
+                rest = &rest[key_len + value_len..];
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Looks through options, newtypes and enum variants to the value they wrap.
+fn unwrap(mut value: &[Token]) -> &[Token] {
+    loop {
+        value = match value.first() {
+            Some(Token::Some | Token::NewtypeStruct { .. } | Token::NewtypeVariant { .. }) => {
+                &value[1..]
+            }
+            Some(Token::Enum { .. }) => {
+                let variant = 1 + value_len(&value[1..]);
+                &value[variant..]
+            }
+            _ => return value,
+        };
+    }
+}
+
+impl Deref for Tokens {
+    type Target = [Token];
+
+    fn deref(&self) -> &[Token] {
+        &self.tokens
+    }
+}
+
+/// A named run of tokens, defined once and spliced into any number of
+/// [`Tokens`] streams.
+///
+/// A failure inside a spliced fragment is reported against a section named
+/// after the fragment.
+///
+/// ```
+/// # use serde_derive::{Deserialize, Serialize};
+/// # use serde_test::{Token, TokenFragment, Tokens};
+/// #
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// let origin = TokenFragment::new(
+///     "origin",
+///     &[
+///         Token::Struct { name: "Point", len: 2 },
+///         Token::Str("x"),
+///         Token::I32(0),
+///         Token::Str("y"),
+///         Token::I32(0),
+///         Token::StructEnd,
+///     ],
+/// );
+///
+/// let origin_point = Point { x: 0, y: 0 };
+/// Tokens::new().splice(&origin).assert_tokens(&origin_point);
+///
+/// let line = [Point { x: 0, y: 0 }, Point { x: 0, y: 0 }];
+/// Tokens::new()
+///     .section("start", &[Token::Tuple { len: 2 }])
+///     .splice(&origin)
+///     .splice(&origin)
+///     .section("end", &[Token::TupleEnd])
+///     .assert_tokens(&line);
+/// ```
+#[derive(Clone, Debug)]
+pub struct TokenFragment {
+    name: String,
+    tokens: Vec<Token>,
+}
+
+impl TokenFragment {
+    /// Creates a fragment called `name` holding `tokens`.
+    #[must_use]
+    pub fn new(name: &str, tokens: &[Token]) -> Self {
+        TokenFragment {
+            name: name.to_owned(),
+            tokens: tokens.to_vec(),
+        }
+    }
+
+    /// The name of the fragment.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Deref for TokenFragment {
+    type Target = [Token];
+
+    fn deref(&self) -> &[Token] {
+        &self.tokens
+    }
+}
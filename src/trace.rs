@@ -0,0 +1,418 @@
+use crate::error::Error;
+use serde::ser::{self, Serialize};
+use std::fmt::{self, Write};
+
+/// A `Serializer` that records every call made to it, one per line, with
+/// nested calls indented underneath the call that started them.
+#[derive(Debug)]
+pub struct TraceSerializer {
+    trace: String,
+    depth: usize,
+}
+
+impl TraceSerializer {
+    pub fn new() -> Self {
+        TraceSerializer {
+            trace: String::new(),
+            depth: 0,
+        }
+    }
+
+    pub fn into_trace(self) -> String {
+        self.trace
+    }
+
+    fn call(&mut self, args: fmt::Arguments) {
+        for _ in 0..self.depth {
+            self.trace.push_str("  ");
+        }
+        self.trace.write_fmt(args).unwrap();
+        self.trace.push('\n');
+    }
+
+    fn nested<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.depth += 1;
+        let result = value.serialize(&mut *self);
+        self.depth -= 1;
+        result
+    }
+
+    fn end(&mut self) {
+        self.call(format_args!("end"));
+        self.depth -= 1;
+    }
+}
+
+/// Removes leading and trailing blank lines and the indentation common to
+/// all remaining lines, so traces can be written as indented literals.
+pub(crate) fn dedent(text: &str) -> String {
+    let lines: Vec<&str> = text
+        .lines()
+        .skip_while(|line| line.trim().is_empty())
+        .collect();
+    let end = lines
+        .iter()
+        .rposition(|line| !line.trim().is_empty())
+        .map_or(0, |i| i + 1);
+    let lines = &lines[..end];
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let mut out = String::new();
+    for line in lines {
+        out.push_str(line.get(indent..).unwrap_or("").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+macro_rules! trace_scalar {
+    ($($method:ident($ty:ty),)*) => {
+        $(
+            fn $method(self, v: $ty) -> Result<(), Error> {
+                self.call(format_args!(concat!(stringify!($method), "({:?})"), v));
+                Ok(())
+            }
+        )*
+    };
+}
+
+impl<'a> ser::Serializer for &'a mut TraceSerializer {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    trace_scalar! {
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_bytes(&[u8]),
+        serialize_unit_struct(&'static str),
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.call(format_args!("serialize_unit"));
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.call(format_args!(
+            "serialize_unit_variant({:?}, {}, {:?})",
+            name, variant_index, variant,
+        ));
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.call(format_args!("serialize_newtype_struct({:?})", name));
+        self.nested(value)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.call(format_args!(
+            "serialize_newtype_variant({:?}, {}, {:?})",
+            name, variant_index, variant,
+        ));
+        self.nested(value)
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        self.call(format_args!("serialize_none"));
+        Ok(())
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.call(format_args!("serialize_some"));
+        self.nested(value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self, Error> {
+        self.call(format_args!("serialize_seq({:?})", len));
+        self.depth += 1;
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self, Error> {
+        self.call(format_args!("serialize_tuple({})", len));
+        self.depth += 1;
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, name: &'static str, len: usize) -> Result<Self, Error> {
+        self.call(format_args!("serialize_tuple_struct({:?}, {})", name, len));
+        self.depth += 1;
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self, Error> {
+        self.call(format_args!(
+            "serialize_tuple_variant({:?}, {}, {:?}, {})",
+            name, variant_index, variant, len,
+        ));
+        self.depth += 1;
+        Ok(self)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self, Error> {
+        self.call(format_args!("serialize_map({:?})", len));
+        self.depth += 1;
+        Ok(self)
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self, Error> {
+        self.call(format_args!("serialize_struct({:?}, {})", name, len));
+        self.depth += 1;
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self, Error> {
+        self.call(format_args!(
+            "serialize_struct_variant({:?}, {}, {:?}, {})",
+            name, variant_index, variant, len,
+        ));
+        self.depth += 1;
+        Ok(self)
+    }
+
+    fn collect_str<T>(self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + fmt::Display,
+    {
+        self.call(format_args!("collect_str({:?})", value.to_string()));
+        Ok(())
+    }
+
+    fn is_human_readable(&self) -> bool {
+        panic!(
+            "Types which have different human-readable and compact representations \
+             must explicitly mark their test cases with `serde_test::Configure`"
+        );
+    }
+}
+
+impl<'a> ser::SerializeSeq for &'a mut TraceSerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.call(format_args!("serialize_element"));
+        self.nested(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        TraceSerializer::end(self);
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeTuple for &'a mut TraceSerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.call(format_args!("serialize_element"));
+        self.nested(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        TraceSerializer::end(self);
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeTupleStruct for &'a mut TraceSerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.call(format_args!("serialize_field"));
+        self.nested(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        TraceSerializer::end(self);
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeTupleVariant for &'a mut TraceSerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.call(format_args!("serialize_field"));
+        self.nested(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        TraceSerializer::end(self);
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeMap for &'a mut TraceSerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.call(format_args!("serialize_key"));
+        self.nested(key)
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.call(format_args!("serialize_value"));
+        self.nested(value)
+    }
+
+    fn serialize_entry<K, V>(&mut self, key: &K, value: &V) -> Result<(), Error>
+    where
+        K: ?Sized + Serialize,
+        V: ?Sized + Serialize,
+    {
+        self.call(format_args!("serialize_entry"));
+        self.nested(key)?;
+        self.nested(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        TraceSerializer::end(self);
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeStruct for &'a mut TraceSerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.call(format_args!("serialize_field({:?})", key));
+        self.nested(value)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), Error> {
+        self.call(format_args!("skip_field({:?})", key));
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), Error> {
+        TraceSerializer::end(self);
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeStructVariant for &'a mut TraceSerializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.call(format_args!("serialize_field({:?})", key));
+        self.nested(value)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), Error> {
+        self.call(format_args!("skip_field({:?})", key));
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), Error> {
+        TraceSerializer::end(self);
+        Ok(())
+    }
+}
+
+/// Returns the trace of `Serializer` calls made while serializing `value`.
+///
+/// See [`assert_ser_trace`] for the format of the trace.
+///
+/// [`assert_ser_trace`]: crate::assert_ser_trace
+pub fn ser_trace<T>(value: &T) -> String
+where
+    T: ?Sized + Serialize,
+{
+    let mut ser = TraceSerializer::new();
+    match value.serialize(&mut ser) {
+        Ok(()) => ser.into_trace(),
+        Err(err) => panic!("value failed to serialize: {}", err),
+    }
+}
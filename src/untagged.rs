@@ -0,0 +1,124 @@
+use crate::config::Config;
+use crate::de::Deserializer;
+use crate::error::Error;
+use crate::mismatch::Mismatch;
+use crate::output::fail;
+use crate::token::Token;
+use crate::trace::dedent;
+use serde::de::{self, Deserialize};
+use std::fmt::Write;
+
+type Attempt<'de> = Box<dyn Fn(&'de [Token]) -> Result<(), Error> + 'de>;
+
+/// Replays how an untagged enum picks a variant for a token stream.
+///
+/// An untagged enum buffers its input and then tries its variants in the
+/// order they are declared, keeping the first that deserializes. The enum
+/// itself discards the error of every variant it rejects, so this lists the
+/// variants with their content types in declaration order and records each
+/// attempt and why it failed, stopping at the first success just as the enum
+/// does. A variant fails if it leaves any of the tokens unconsumed.
+///
+/// The trace has one line per attempted variant, `Name: ok` for the variant
+/// that was picked and `Name: <error>` for each one rejected before it.
+///
+/// ```
+/// # use serde_derive::Deserialize;
+/// # use serde_test::{assert_de_tokens, Token, UntaggedTrace};
+/// #
+/// #[derive(Deserialize, PartialEq, Debug)]
+/// #[serde(untagged)]
+/// enum Id {
+///     Number(u32),
+///     Name(String),
+/// }
+///
+/// let tokens = [Token::Str("root")];
+/// assert_de_tokens(&Id::Name("root".to_owned()), &tokens);
+///
+/// UntaggedTrace::new(&tokens)
+///     .variant::<u32>("Number")
+///     .variant::<String>("Name")
+///     .assert_trace(
+///         r#"
+///             Number: invalid type: string "root", expected u32
+///             Name: ok
+///         "#,
+///     );
+/// ```
+pub struct UntaggedTrace<'de> {
+    tokens: &'de [Token],
+    variants: Vec<(&'static str, Attempt<'de>)>,
+}
+
+impl<'de> UntaggedTrace<'de> {
+    /// Creates a trace of the variants attempted for `tokens`.
+    #[must_use]
+    pub fn new(tokens: &'de [Token]) -> Self {
+        UntaggedTrace {
+            tokens,
+            variants: Vec::new(),
+        }
+    }
+
+    /// Declares the next variant of the enum, called `name`, whose content
+    /// deserializes as `T`.
+    ///
+    /// Use `()` for a unit variant.
+    #[must_use]
+    pub fn variant<T>(mut self, name: &'static str) -> Self
+    where
+        T: Deserialize<'de> + 'de,
+    {
+        let attempt = |tokens: &'de [Token]| {
+            let mut de = Deserializer::new(tokens);
+            T::deserialize(&mut de)?;
+            match de.remaining() {
+                0 => Ok(()),
+                n => Err(de::Error::custom(format_args!(
+                    "{} tokens left over, starting with {}",
+                    n,
+                    tokens[tokens.len() - n],
+                ))),
+            }
+        };
+        self.variants.push((name, Box::new(attempt)));
+        self
+    }
+
+    /// Returns the trace of the variants attempted, in order.
+    #[must_use]
+    pub fn trace(&self) -> String {
+        let mut trace = String::new();
+        for (name, attempt) in &self.variants {
+            match attempt(self.tokens) {
+                Ok(()) => {
+                    let _ = writeln!(trace, "{}: ok", name);
+                    break;
+                }
+                Err(err) => {
+                    let _ = writeln!(trace, "{}: {}", name, err);
+                }
+            }
+        }
+        trace
+    }
+
+    /// Asserts that the variants are attempted as described by `trace`.
+    ///
+    /// Indentation common to all lines of `trace` is ignored so it can be
+    /// written as an indented string literal.
+    #[track_caller]
+    pub fn assert_trace(&self, trace: &str) {
+        let actual = self.trace();
+        let expected = dedent(trace);
+        if actual != expected {
+            let mismatch = Mismatch::new(format!(
+                "untagged variant attempts did not match the expected trace\n\
+                 expected:\n{}\nactual:\n{}",
+                expected, actual,
+            ));
+            fail(&Config::new(), self.tokens, mismatch);
+        }
+    }
+}
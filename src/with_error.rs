@@ -0,0 +1,422 @@
+use crate::de::Deserializer;
+use crate::error::Error;
+use crate::ser::Serializer;
+use serde::de::{self, Visitor};
+use serde::ser::{self, Serialize};
+use std::marker::PhantomData;
+
+/// Wraps a [`Serializer`] or [`Deserializer`] so that it fails with an error
+/// type of the caller's choosing.
+///
+/// Library code is sometimes written against a concrete error type, as in
+/// `S: Serializer<Error = MyError>`. The wrapper checks calls against the
+/// expected tokens exactly as the serializer or deserializer it wraps does,
+/// and converts any failure into `E` through `ser::Error::custom` or
+/// `de::Error::custom`, keeping its message.
+///
+/// ```
+/// use serde::ser::{Error as _, Serialize, Serializer as _};
+/// use serde_test::raw::{Serializer, WithError};
+/// use serde_test::Token;
+/// use std::fmt::{self, Display};
+///
+/// #[derive(Debug)]
+/// struct MyError(String);
+///
+/// impl Display for MyError {
+///     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+///         f.write_str(&self.0)
+///     }
+/// }
+///
+/// impl std::error::Error for MyError {}
+///
+/// impl serde::ser::Error for MyError {
+///     fn custom<T: Display>(msg: T) -> Self {
+///         MyError(msg.to_string())
+///     }
+/// }
+///
+/// fn write_port<S>(serializer: S, port: u16) -> Result<S::Ok, MyError>
+/// where
+///     S: serde::Serializer<Error = MyError>,
+/// {
+///     if port == 0 {
+///         return Err(MyError("port must be nonzero".to_owned()));
+///     }
+///     serializer.serialize_u16(port)
+/// }
+///
+/// let tokens = [Token::U16(8080)];
+/// let mut ser = Serializer::new(&tokens);
+/// write_port(WithError::new(&mut ser), 8080).unwrap();
+///
+/// let mut ser = Serializer::new(&tokens);
+/// let err = write_port(WithError::new(&mut ser), 0).unwrap_err();
+/// assert_eq!(err.0, "port must be nonzero");
+/// ```
+pub struct WithError<S, E> {
+    inner: S,
+    error: PhantomData<E>,
+}
+
+impl<S, E> WithError<S, E> {
+    /// Wraps `inner`, which is a `&mut Serializer`, a `&mut Deserializer`, or
+    /// one of their compound serializers.
+    pub fn new(inner: S) -> Self {
+        WithError {
+            inner,
+            error: PhantomData,
+        }
+    }
+}
+
+macro_rules! forward_ser {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            fn $method(self, $($arg: $ty),*) -> Result<(), E> {
+                self.inner.$method($($arg),*).map_err(ser::Error::custom)
+            }
+        )*
+    };
+}
+
+macro_rules! forward_ser_compound {
+    ($($method:ident($($arg:ident: $ty:ty),*) -> $compound:ident;)*) => {
+        $(
+            fn $method(self, $($arg: $ty),*) -> Result<Self::$compound, E> {
+                match self.inner.$method($($arg),*) {
+                    Ok(compound) => Ok(WithError::new(compound)),
+                    Err(err) => Err(ser::Error::custom(err)),
+                }
+            }
+        )*
+    };
+}
+
+impl<'s, 'a, E> ser::Serializer for WithError<&'s mut Serializer<'a>, E>
+where
+    E: ser::Error,
+{
+    type Ok = ();
+    type Error = E;
+
+    type SerializeSeq = WithError<<&'s mut Serializer<'a> as ser::Serializer>::SerializeSeq, E>;
+    type SerializeTuple = WithError<<&'s mut Serializer<'a> as ser::Serializer>::SerializeTuple, E>;
+    type SerializeTupleStruct =
+        WithError<<&'s mut Serializer<'a> as ser::Serializer>::SerializeTupleStruct, E>;
+    type SerializeTupleVariant =
+        WithError<<&'s mut Serializer<'a> as ser::Serializer>::SerializeTupleVariant, E>;
+    type SerializeMap = WithError<<&'s mut Serializer<'a> as ser::Serializer>::SerializeMap, E>;
+    type SerializeStruct =
+        WithError<<&'s mut Serializer<'a> as ser::Serializer>::SerializeStruct, E>;
+    type SerializeStructVariant =
+        WithError<<&'s mut Serializer<'a> as ser::Serializer>::SerializeStructVariant, E>;
+
+    forward_ser! {
+        serialize_bool(v: bool);
+        serialize_i8(v: i8);
+        serialize_i16(v: i16);
+        serialize_i32(v: i32);
+        serialize_i64(v: i64);
+        serialize_i128(v: i128);
+        serialize_u8(v: u8);
+        serialize_u16(v: u16);
+        serialize_u32(v: u32);
+        serialize_u64(v: u64);
+        serialize_u128(v: u128);
+        serialize_f32(v: f32);
+        serialize_f64(v: f64);
+        serialize_char(v: char);
+        serialize_str(v: &str);
+        serialize_bytes(v: &[u8]);
+        serialize_unit();
+        serialize_unit_struct(name: &'static str);
+        serialize_unit_variant(name: &'static str, variant_index: u32, variant: &'static str);
+        serialize_none();
+    }
+
+    forward_ser_compound! {
+        serialize_seq(len: Option<usize>) -> SerializeSeq;
+        serialize_tuple(len: usize) -> SerializeTuple;
+        serialize_tuple_struct(name: &'static str, len: usize) -> SerializeTupleStruct;
+        serialize_tuple_variant(
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+            len: usize
+        ) -> SerializeTupleVariant;
+        serialize_map(len: Option<usize>) -> SerializeMap;
+        serialize_struct(name: &'static str, len: usize) -> SerializeStruct;
+        serialize_struct_variant(
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+            len: usize
+        ) -> SerializeStructVariant;
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<(), E>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner
+            .serialize_newtype_struct(name, value)
+            .map_err(ser::Error::custom)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), E>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner
+            .serialize_newtype_variant(name, variant_index, variant, value)
+            .map_err(ser::Error::custom)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<(), E>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner.serialize_some(value).map_err(ser::Error::custom)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        ser::Serializer::is_human_readable(&self.inner)
+    }
+}
+
+impl<C, E> ser::SerializeSeq for WithError<C, E>
+where
+    C: ser::SerializeSeq<Error = Error>,
+    E: ser::Error,
+{
+    type Ok = C::Ok;
+    type Error = E;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), E>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner
+            .serialize_element(value)
+            .map_err(ser::Error::custom)
+    }
+
+    fn end(self) -> Result<C::Ok, E> {
+        self.inner.end().map_err(ser::Error::custom)
+    }
+}
+
+impl<C, E> ser::SerializeTuple for WithError<C, E>
+where
+    C: ser::SerializeTuple<Error = Error>,
+    E: ser::Error,
+{
+    type Ok = C::Ok;
+    type Error = E;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), E>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner
+            .serialize_element(value)
+            .map_err(ser::Error::custom)
+    }
+
+    fn end(self) -> Result<C::Ok, E> {
+        self.inner.end().map_err(ser::Error::custom)
+    }
+}
+
+impl<C, E> ser::SerializeTupleStruct for WithError<C, E>
+where
+    C: ser::SerializeTupleStruct<Error = Error>,
+    E: ser::Error,
+{
+    type Ok = C::Ok;
+    type Error = E;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), E>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner
+            .serialize_field(value)
+            .map_err(ser::Error::custom)
+    }
+
+    fn end(self) -> Result<C::Ok, E> {
+        self.inner.end().map_err(ser::Error::custom)
+    }
+}
+
+impl<C, E> ser::SerializeTupleVariant for WithError<C, E>
+where
+    C: ser::SerializeTupleVariant<Error = Error>,
+    E: ser::Error,
+{
+    type Ok = C::Ok;
+    type Error = E;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), E>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner
+            .serialize_field(value)
+            .map_err(ser::Error::custom)
+    }
+
+    fn end(self) -> Result<C::Ok, E> {
+        self.inner.end().map_err(ser::Error::custom)
+    }
+}
+
+impl<C, E> ser::SerializeMap for WithError<C, E>
+where
+    C: ser::SerializeMap<Error = Error>,
+    E: ser::Error,
+{
+    type Ok = C::Ok;
+    type Error = E;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), E>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner.serialize_key(key).map_err(ser::Error::custom)
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), E>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner
+            .serialize_value(value)
+            .map_err(ser::Error::custom)
+    }
+
+    fn end(self) -> Result<C::Ok, E> {
+        self.inner.end().map_err(ser::Error::custom)
+    }
+}
+
+impl<C, E> ser::SerializeStruct for WithError<C, E>
+where
+    C: ser::SerializeStruct<Error = Error>,
+    E: ser::Error,
+{
+    type Ok = C::Ok;
+    type Error = E;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), E>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner
+            .serialize_field(key, value)
+            .map_err(ser::Error::custom)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), E> {
+        self.inner.skip_field(key).map_err(ser::Error::custom)
+    }
+
+    fn end(self) -> Result<C::Ok, E> {
+        self.inner.end().map_err(ser::Error::custom)
+    }
+}
+
+impl<C, E> ser::SerializeStructVariant for WithError<C, E>
+where
+    C: ser::SerializeStructVariant<Error = Error>,
+    E: ser::Error,
+{
+    type Ok = C::Ok;
+    type Error = E;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), E>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner
+            .serialize_field(key, value)
+            .map_err(ser::Error::custom)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), E> {
+        self.inner.skip_field(key).map_err(ser::Error::custom)
+    }
+
+    fn end(self) -> Result<C::Ok, E> {
+        self.inner.end().map_err(ser::Error::custom)
+    }
+}
+
+macro_rules! forward_de {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, E>
+            where
+                V: Visitor<'de>,
+            {
+                self.inner
+                    .$method($($arg,)* visitor)
+                    .map_err(de::Error::custom)
+            }
+        )*
+    };
+}
+
+impl<'s, 'de, E> de::Deserializer<'de> for WithError<&'s mut Deserializer<'de>, E>
+where
+    E: de::Error,
+{
+    type Error = E;
+
+    forward_de! {
+        deserialize_any();
+        deserialize_bool();
+        deserialize_i8();
+        deserialize_i16();
+        deserialize_i32();
+        deserialize_i64();
+        deserialize_i128();
+        deserialize_u8();
+        deserialize_u16();
+        deserialize_u32();
+        deserialize_u64();
+        deserialize_u128();
+        deserialize_f32();
+        deserialize_f64();
+        deserialize_char();
+        deserialize_str();
+        deserialize_string();
+        deserialize_bytes();
+        deserialize_byte_buf();
+        deserialize_option();
+        deserialize_unit();
+        deserialize_unit_struct(name: &'static str);
+        deserialize_newtype_struct(name: &'static str);
+        deserialize_seq();
+        deserialize_tuple(len: usize);
+        deserialize_tuple_struct(name: &'static str, len: usize);
+        deserialize_map();
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]);
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]);
+        deserialize_identifier();
+        deserialize_ignored_any();
+    }
+
+    fn is_human_readable(&self) -> bool {
+        de::Deserializer::is_human_readable(&self.inner)
+    }
+}